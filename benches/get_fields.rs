@@ -0,0 +1,133 @@
+use apache_avro::{
+    types::{Record, Value},
+    Reader, Schema, Writer,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+use explore_avro::{CliService, GetOptions};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+const FIELDS: usize = 200;
+const RECORDS: usize = 10_000;
+
+/// A file of records with many long fields, to read back in the benchmarks, removed when
+/// dropped
+struct WideFile(PathBuf);
+
+impl WideFile {
+    fn write() -> Self {
+        let fields: Vec<String> = (0..FIELDS)
+            .map(|i| format!(r#"{{"name": "f{}", "type": "long"}}"#, i))
+            .collect();
+        let schema = Schema::parse_str(&format!(
+            r#"{{"type": "record", "name": "wide", "fields": [{}]}}"#,
+            fields.join(", ")
+        ))
+        .unwrap();
+
+        // Named after the process, so that concurrent runs don't write over each other
+        let path = std::env::temp_dir().join(format!(
+            "explore-avro-bench-wide-{}.avro",
+            std::process::id()
+        ));
+        let file = WideFile(path);
+        let mut writer = Writer::new(&schema, File::create(&file.0).unwrap());
+        for n in 0..RECORDS {
+            let mut record = Record::new(&schema).unwrap();
+            for i in 0..FIELDS {
+                record.put(&format!("f{}", i), (n * i) as i64);
+            }
+            writer.append(record).unwrap();
+        }
+        writer.flush().unwrap();
+        file
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for WideFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn field_sets() -> [(&'static str, Vec<String>); 3] {
+    [
+        ("first field", vec!["f0".to_owned()]),
+        ("last field", vec![format!("f{}", FIELDS - 1)]),
+        (
+            "every tenth field",
+            (0..FIELDS).step_by(10).map(|i| format!("f{}", i)).collect(),
+        ),
+    ]
+}
+
+/// Decode every record and pick fields out of it with `pick`
+fn read_with(path: &Path, mut pick: impl FnMut(&[(String, Value)]) -> usize) -> usize {
+    let reader = Reader::new(BufReader::new(File::open(path).unwrap())).unwrap();
+    reader
+        .map(|record| match record.unwrap() {
+            Value::Record(values) => pick(&values),
+            _ => 0,
+        })
+        .sum()
+}
+
+fn get_fields(c: &mut Criterion) {
+    let file = WideFile::write();
+    let path = file.path().to_string_lossy().into_owned();
+    let options = GetOptions::default();
+
+    let mut group = c.benchmark_group("get_fields");
+    group.sample_size(20);
+    for (name, fields) in field_sets() {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                CliService::from(path.clone())
+                    .unwrap()
+                    .get_fields(&fields, &options)
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+
+    // Looking fields up by name in every record, as before, against indexing them at the
+    // position found once in the schema
+    let mut group = c.benchmark_group("field_lookup");
+    group.sample_size(20);
+    for (name, fields) in field_sets() {
+        group.bench_function(format!("{}, find by name", name), |b| {
+            b.iter(|| {
+                read_with(file.path(), |values| {
+                    fields
+                        .iter()
+                        .filter_map(|field| values.iter().find(|(name, _)| name == field))
+                        .count()
+                })
+            })
+        });
+        let positions: Vec<usize> = fields
+            .iter()
+            .map(|field| field[1..].parse().unwrap())
+            .collect();
+        group.bench_function(format!("{}, by position", name), |b| {
+            b.iter(|| {
+                read_with(file.path(), |values| {
+                    positions
+                        .iter()
+                        .filter_map(|&position| values.get(position))
+                        .count()
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, get_fields);
+criterion_main!(benches);
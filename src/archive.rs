@@ -0,0 +1,49 @@
+//! Reading Avro files bundled in zip archives
+
+use miette::{IntoDiagnostic, Result, WrapErr as _};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+const ZIP_MAGIC: &[u8; 4] = b"PK\x03\x04";
+
+/// Whether a file is a zip archive, going by its extension or its first bytes
+pub(crate) fn is_zip(path: &Path, file: &mut File) -> Result<bool> {
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+    {
+        return Ok(true);
+    }
+
+    let mut magic = [0; 4];
+    let is_zip = file.read_exact(&mut magic).is_ok() && &magic == ZIP_MAGIC;
+    file.seek(SeekFrom::Start(0)).into_diagnostic()?;
+    Ok(is_zip)
+}
+
+/// Extract the `.avro` entries of a zip archive into memory.
+/// Each entry is named by its path inside the archive, under the archive's path.
+pub(crate) fn avro_entries(path: &Path, file: File) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+    let mut archive = zip::ZipArchive::new(file)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Could not read zip archive {}", path.display()))?;
+
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).into_diagnostic()?;
+        let name = entry.name().to_owned();
+        if entry.is_dir() || !name.to_ascii_lowercase().ends_with(".avro") {
+            continue;
+        }
+
+        let mut bytes = Vec::with_capacity(entry.size() as usize);
+        entry
+            .read_to_end(&mut bytes)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Could not extract {} from {}", name, path.display()))?;
+        entries.push((path.join(name), bytes));
+    }
+
+    Ok(entries)
+}
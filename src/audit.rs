@@ -0,0 +1,127 @@
+use crate::avro_value::AvroValue;
+use crate::cli::{CliService, FieldOrder, GetOptions};
+use crate::schema::type_name;
+use apache_avro::{schema::RecordSchema, types::Value, Schema};
+use miette::Result;
+
+/// How often a field is missing or null over the records audited
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Presence {
+    /// Number of records audited
+    pub records: u64,
+    /// Records that don't have the field (N/A)
+    pub missing: u64,
+    /// Records where the field is null
+    pub null: u64,
+    /// For a union field, the number of records holding each branch, by branch index
+    pub branches: Vec<u64>,
+}
+
+impl Presence {
+    pub fn add(&mut self, value: &AvroValue) {
+        self.records += 1;
+        match value {
+            AvroValue::Na => self.missing += 1,
+            AvroValue::Value(value) if is_null(value) => self.null += 1,
+            AvroValue::Value(_) => {}
+        }
+        if let AvroValue::Value(Value::Union(index, _)) = value {
+            let index = *index as usize;
+            if self.branches.len() <= index {
+                self.branches.resize(index + 1, 0);
+            }
+            self.branches[index] += 1;
+        }
+    }
+
+    /// Fraction of the records that don't have the field, None without records
+    pub fn missing_fraction(&self) -> Option<f64> {
+        (self.records > 0).then(|| self.missing as f64 / self.records as f64)
+    }
+
+    /// Fraction of the records where the field is null, None without records
+    pub fn null_fraction(&self) -> Option<f64> {
+        (self.records > 0).then(|| self.null as f64 / self.records as f64)
+    }
+
+    /// Whether no record audited has a value for the field
+    pub fn always_empty(&self) -> bool {
+        self.missing + self.null == self.records
+    }
+}
+
+/// Count how often each field is missing or null, to spot columns that are always empty.
+///
+/// Without `fields`, every field of every file is audited. `options.take` limits the audit
+/// to the first records.
+pub fn audit(
+    avro: &mut CliService,
+    fields: &[String],
+    options: &GetOptions,
+) -> Result<Vec<(String, Presence)>> {
+    let fields = if fields.is_empty() {
+        avro.get_field_names_across_files(FieldOrder::Schema)?
+    } else {
+        fields.to_vec()
+    };
+
+    let mut presence = vec![Presence::default(); fields.len()];
+    for row in avro.get_fields(&fields, options)? {
+        for (presence, value) in presence.iter_mut().zip(&row) {
+            presence.add(value.value());
+        }
+    }
+
+    Ok(fields.into_iter().zip(presence).collect())
+}
+
+/// Names of the branches of a top-level union field, as its type names, or none when
+/// the schema has no such union field
+pub fn union_branch_names(schema: &Schema, field: &str) -> Vec<String> {
+    let Schema::Record(RecordSchema { fields, lookup, .. }) = schema else {
+        return Vec::new();
+    };
+    match lookup.get(field).map(|&i| &fields[i].schema) {
+        Some(Schema::Union(union)) => union.variants().iter().map(type_name).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn is_null(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Union(_, value) => is_null(value),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presence() {
+        let null = AvroValue::from(Value::Union(0, Box::new(Value::Null)));
+        let present = AvroValue::from(Value::Union(1, Box::new(Value::Long(7))));
+
+        let mut presence = Presence::default();
+        for value in [AvroValue::na(), null.clone(), AvroValue::from(Value::Null)] {
+            presence.add(&value);
+        }
+        assert!(presence.always_empty());
+        assert_eq!(presence.missing, 1);
+        assert_eq!(presence.null, 2);
+        assert_eq!(presence.branches, vec![1]);
+
+        presence.add(&present);
+        assert!(!presence.always_empty());
+        assert_eq!(presence.records, 4);
+        assert_eq!(presence.missing_fraction(), Some(0.25));
+        assert_eq!(presence.null_fraction(), Some(0.5));
+        assert_eq!(presence.branches, vec![1, 1]);
+
+        let empty = Presence::default();
+        assert!(empty.always_empty());
+        assert_eq!(empty.null_fraction(), None);
+    }
+}
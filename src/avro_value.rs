@@ -1,14 +1,90 @@
-use apache_avro::types::Value;
+use apache_avro::{types::Value, Duration};
 use jiff::{tz::TimeZone, Span};
 use miette::{IntoDiagnostic, Result};
 use num_bigint::BigInt;
 use std::fmt;
 
-pub(crate) const NULL: &'static str = "null";
-pub(crate) const NA: &'static str = "N/A";
+pub const NULL: &'static str = "null";
+pub const NA: &'static str = "N/A";
 
+/// Options for rendering values as text, in tables and CSV.
+/// Only those noted also apply to JSON
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    /// Render records, maps and arrays nested deeper than this as placeholders
+    pub max_depth: Option<usize>,
+    /// Render decimals as floating point numbers. This loses precision past about
+    /// 15 significant digits, so the output must not be relied on for exact values
+    pub decimal_as_float: bool,
+    /// Render fixed values as UTF-8 strings when they are valid UTF-8, and as hex otherwise.
+    /// Also applies to JSON
+    pub fixed_as_string: bool,
+    /// Group the digits of ints, longs and decimals in the style of this locale
+    pub group_digits: Option<NumberLocale>,
+    /// Render timestamps of every precision in this one format. Local timestamps are taken
+    /// as UTC. Also applies to JSON
+    pub normalize_timestamps: Option<TimestampFormat>,
+    /// Write longs that JavaScript can't represent exactly, past ±(2^53 - 1), as JSON
+    /// strings. Only applies to JSON
+    pub longs_as_strings: bool,
+    /// Unions hold a record naming their branch, as `schema::annotate_unions` makes them:
+    /// render them as `branch=value`. JSON keeps the record, as in Avro's JSON encoding
+    pub annotated_unions: bool,
+}
+
+/// Largest integer a double, and so a JavaScript number, holds exactly
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// A single representation for timestamps, whatever their precision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TimestampFormat {
+    /// RFC 3339, e.g. 2023-01-01T12:00:00.5Z
+    Iso,
+    /// Milliseconds since the Unix epoch, as an integer
+    EpochMs,
+    /// Whole seconds since the Unix epoch, as an integer
+    EpochS,
+}
+
+impl TimestampFormat {
+    /// The integer for the epoch formats, None for `Iso`
+    fn epoch(self, timestamp: jiff::Timestamp) -> Option<i64> {
+        match self {
+            Self::Iso => None,
+            Self::EpochMs => Some(timestamp.as_millisecond()),
+            Self::EpochS => Some(timestamp.as_second()),
+        }
+    }
+}
+
+/// Separator style for grouped digits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum NumberLocale {
+    /// 1,234,567.89
+    #[default]
+    En,
+    /// 1.234.567,89
+    De,
+    /// 1 234 567,89
+    Fr,
+    /// 1'234'567.89
+    Ch,
+}
+
+impl NumberLocale {
+    fn separators(self) -> (char, char) {
+        match self {
+            Self::En => (',', '.'),
+            Self::De => ('.', ','),
+            Self::Fr => ('\u{202f}', ','),
+            Self::Ch => ('\'', '.'),
+        }
+    }
+}
+
+/// A value read from an Avro record, or `Na` when the record doesn't have the field
 #[derive(Debug, Clone)]
-pub(crate) enum AvroValue {
+pub enum AvroValue {
     Value(Value),
     Na,
 }
@@ -26,10 +102,24 @@ impl<'a> AvroValue {
         format!("{}", self)
     }
     
-    pub fn to_json(&self) -> Result<serde_json::Value> {
+    pub fn format(&self, options: &FormatOptions) -> Result<String> {
+        match self {
+            Self::Na => Ok(NA.to_owned()),
+            Self::Value(v) => format_avro_value(v, options, 0),
+        }
+    }
+
+    pub fn to_json(&self, options: &FormatOptions) -> Result<serde_json::Value> {
         match self {
             Self::Na => Ok(serde_json::Value::Null),
-            Self::Value(v) => to_json(v),
+            Self::Value(v) => to_json(v, options),
+        }
+    }
+
+    pub fn to_json5(&self, options: &FormatOptions) -> Result<String> {
+        match self {
+            Self::Na => Ok(NULL.to_owned()),
+            Self::Value(v) => to_json5(v, options),
         }
     }
 }
@@ -37,18 +127,34 @@ impl<'a> AvroValue {
 impl<'a> fmt::Display for AvroValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            AvroValue::Value(v) => write!(f, "{}", format_avro_value(v).map_err(|_| fmt::Error)?),
+            AvroValue::Value(v) => write!(
+                f,
+                "{}",
+                format_avro_value(v, &FormatOptions::default(), 0).map_err(|_| fmt::Error)?
+            ),
             AvroValue::Na => write!(f, "{}", NA),
         }
     }
 }
 
-fn format_avro_value(value: &Value) -> Result<String> {
+fn format_avro_value(value: &Value, options: &FormatOptions, depth: usize) -> Result<String> {
+    if let Some(format) = options.normalize_timestamps {
+        if let Some(timestamp) = timestamp_instant(value)? {
+            return Ok(match format.epoch(timestamp) {
+                Some(epoch) => epoch.to_string(),
+                None => timestamp.to_string(),
+            });
+        }
+    }
+    let too_deep = options.max_depth.is_some_and(|max| depth > max);
     Ok(match value {
+        Value::Array(_) if too_deep => "[...]".to_owned(),
+        Value::Map(_) | Value::Record(_) if too_deep => "{...}".to_owned(),
+
         Value::Array(a) => format!(
             "{}",
             a.iter()
-                .map(|v| format_avro_value(v))
+                .map(|v| format_avro_value(v, options, depth + 1))
                 .collect::<Result<Vec<String>>>()?
                 .join(", ")
         ),
@@ -62,6 +168,7 @@ fn format_avro_value(value: &Value) -> Result<String> {
         Value::Boolean(b) => format!("{}", b),
         Value::Double(d) => format!("{}", d),
         Value::Enum(id, desc) => format!("{} ({})", id, desc),
+        Value::Fixed(_, f) if options.fixed_as_string => fixed_to_string(f),
         Value::Fixed(_, f) => format!(
             "{}",
             f.iter()
@@ -70,12 +177,14 @@ fn format_avro_value(value: &Value) -> Result<String> {
                 .join(", ")
         ),
         Value::Float(f) => format!("{}", f),
-        Value::Int(i) => format!("{}", i),
-        Value::Long(l) => format!("{}", l),
+        Value::Int(i) => group_digits(i.to_string(), options),
+        Value::Long(l) => group_digits(l.to_string(), options),
         Value::Map(m) => format!(
             "{}",
             m.iter()
-                .map(|(k, v)| format_avro_value(v).map(|v| format!("{}: {}", k, v)))
+                .map(|(k, v)| {
+                    format_avro_value(v, options, depth + 1).map(|v| format!("{}: {}", k, v))
+                })
                 .collect::<Result<Vec<String>>>()?
                 .join(", ")
         ),
@@ -83,17 +192,24 @@ fn format_avro_value(value: &Value) -> Result<String> {
         Value::Record(m) => format!(
             "{}",
             m.iter()
-                .map(|(k, v)| format_avro_value(v).map(|v| format!("{}: {}", k, v)))
+                .map(|(k, v)| {
+                    format_avro_value(v, options, depth + 1).map(|v| format!("{}: {}", k, v))
+                })
                 .collect::<Result<Vec<String>>>()?
                 .join(", ")
         ),
         Value::String(s) => s.clone(),
 
-        Value::Date(s) => jiff::Timestamp::from_second((*s).into())
-            .into_diagnostic()?
-            .to_string(),
-        Value::Decimal(decimal) => BigInt::from(decimal.clone()).to_string(),
-        Value::BigDecimal(big_decimal) => big_decimal.as_bigint_and_exponent().0.to_string(),
+        Value::Date(days) => date_from_days(*days)?.to_string(),
+        Value::Decimal(decimal) if options.decimal_as_float => group_digits(
+            decimal_to_float(&BigInt::from(decimal.clone()).to_string())?,
+            options,
+        ),
+        Value::Decimal(decimal) => group_digits(BigInt::from(decimal.clone()).to_string(), options),
+        Value::BigDecimal(big_decimal) if options.decimal_as_float => {
+            group_digits(decimal_to_float(&big_decimal.to_string())?, options)
+        }
+        Value::BigDecimal(big_decimal) => group_digits(big_decimal.to_string(), options),
         Value::TimeMillis(ms) => jiff::civil::Time::MIN
             .saturating_add(Span::new().milliseconds(*ms))
             .to_string(),
@@ -103,10 +219,10 @@ fn format_avro_value(value: &Value) -> Result<String> {
         Value::TimestampMillis(ms) => jiff::Timestamp::from_millisecond(*ms)
             .into_diagnostic()?
             .to_string(),
-        Value::TimestampMicros(us) => jiff::Timestamp::from_millisecond(*us)
+        Value::TimestampMicros(us) => jiff::Timestamp::from_microsecond(*us)
             .into_diagnostic()?
             .to_string(),
-        Value::TimestampNanos(ns) => jiff::Timestamp::from_millisecond(*ns)
+        Value::TimestampNanos(ns) => jiff::Timestamp::from_nanosecond((*ns).into())
             .into_diagnostic()?
             .to_string(),
         Value::LocalTimestampMillis(ms) => jiff::Timestamp::from_millisecond(*ms)
@@ -121,37 +237,108 @@ fn format_avro_value(value: &Value) -> Result<String> {
             .into_diagnostic()?
             .to_zoned(TimeZone::try_system().unwrap_or(TimeZone::UTC))
             .to_string(),
-        Value::Duration(duration) => (jiff::Span::new()
-            .months(u32::from(duration.months()))
-            .checked_add(jiff::Span::new().days(u32::from(duration.days())))
-            .into_diagnostic()?
-            .checked_add(jiff::Span::new().milliseconds(u32::from(duration.millis())))
-            .into_diagnostic()?)
-        .to_string(),
+        Value::Duration(duration) => format_duration(duration),
         Value::Uuid(uuid) => uuid.to_string(),
 
-        Value::Union(_, value) => format_avro_value(&*value)?,
+        Value::Union(_, value) => match &**value {
+            Value::Record(fields) if options.annotated_unions && fields.len() == 1 => {
+                let (branch, value) = &fields[0];
+                format!("{}={}", branch, format_avro_value(value, options, depth)?)
+            }
+            value => format_avro_value(value, options, depth)?,
+        },
     })
 }
 
-pub fn to_json(value: &Value) -> Result<serde_json::Value> {
+/// The calendar date of an Avro date, which counts days since the Unix epoch
+fn date_from_days(days: i32) -> Result<jiff::civil::Date> {
+    jiff::civil::date(1970, 1, 1)
+        .checked_add(Span::new().try_days(days).into_diagnostic()?)
+        .into_diagnostic()
+}
+
+/// Render a duration as its three components, e.g. `12 months, 5 days, 3600000 ms`.
+/// They are kept apart because they don't convert into each other: months vary in days,
+/// and days in milliseconds around daylight saving changes
+fn format_duration(duration: &Duration) -> String {
+    let plural = |n: u32, unit: &str| match n {
+        1 => format!("1 {}", unit),
+        n => format!("{} {}s", n, unit),
+    };
+    format!(
+        "{}, {}, {} ms",
+        plural(duration.months().into(), "month"),
+        plural(duration.days().into(), "day"),
+        u32::from(duration.millis())
+    )
+}
+
+/// Render a duration in ISO 8601, e.g. `P12M5DT3600S`, keeping its components apart
+fn iso_duration(duration: &Duration) -> String {
+    let millis = u32::from(duration.millis());
+    let seconds = match millis % 1000 {
+        0 => (millis / 1000).to_string(),
+        fraction => format!("{}.{:03}", millis / 1000, fraction)
+            .trim_end_matches('0')
+            .to_owned(),
+    };
+    format!(
+        "P{}M{}DT{}S",
+        u32::from(duration.months()),
+        u32::from(duration.days()),
+        seconds
+    )
+}
+
+/// Render a value as text, as shown in tables and CSV
+pub fn format_value(value: &Value, options: &FormatOptions) -> Result<String> {
+    format_avro_value(value, options, 0)
+}
+
+/// The instant a timestamp of any precision stands for, taking local timestamps as UTC.
+/// None for values that aren't timestamps
+fn timestamp_instant(value: &Value) -> Result<Option<jiff::Timestamp>> {
+    Ok(Some(match value {
+        Value::TimestampMillis(ms) | Value::LocalTimestampMillis(ms) => {
+            jiff::Timestamp::from_millisecond(*ms).into_diagnostic()?
+        }
+        Value::TimestampMicros(us) | Value::LocalTimestampMicros(us) => {
+            jiff::Timestamp::from_microsecond(*us).into_diagnostic()?
+        }
+        Value::TimestampNanos(ns) | Value::LocalTimestampNanos(ns) => {
+            jiff::Timestamp::from_nanosecond((*ns).into()).into_diagnostic()?
+        }
+        _ => return Ok(None),
+    }))
+}
+
+/// Convert a value to JSON
+pub fn to_json(value: &Value, options: &FormatOptions) -> Result<serde_json::Value> {
+    if let Some(format) = options.normalize_timestamps {
+        if let Some(timestamp) = timestamp_instant(value)? {
+            return Ok(match format.epoch(timestamp) {
+                Some(epoch) => serde_json::Value::Number(epoch.into()),
+                None => serde_json::Value::String(timestamp.to_string()),
+            });
+        }
+    }
     Ok(match value {
         Value::Array(a) => serde_json::Value::Array(
             a.iter()
-                .map(|v| to_json(v))
+                .map(|v| to_json(v, options))
                 .collect::<Result<Vec<serde_json::Value>>>()?,
         ),
         Value::Map(m) => serde_json::Value::Object(
             m.iter()
-                .map(|(k, v)| to_json(v).map(|v| (k.to_owned(), v)))
+                .map(|(k, v)| to_json(v, options).map(|v| (k.to_owned(), v)))
                 .collect::<Result<_>>()?,
         ),
         Value::Record(m) => serde_json::Value::Object(
             m.iter()
-                .map(|(k, v)| to_json(v).map(|v| (k.to_owned(), v)))
+                .map(|(k, v)| to_json(v, options).map(|v| (k.to_owned(), v)))
                 .collect::<Result<_>>()?,
         ),
-        Value::Union(_, value) => to_json(&*value)?,
+        Value::Union(_, value) => to_json(&*value, options)?,
         Value::Null => serde_json::Value::Null,
 
         Value::Bytes(b) => serde_json::Value::Array(
@@ -160,34 +347,34 @@ pub fn to_json(value: &Value) -> Result<serde_json::Value> {
                 .collect(),
         ),
         Value::Boolean(b) => serde_json::Value::Bool(*b),
-        Value::Double(d) => serde_json::Value::Number(
-            serde_json::Number::from_f64(*d).unwrap_or(serde_json::Number::from_f64(0.0).unwrap()),
-        ),
-        Value::Float(f) => serde_json::Value::Number(
-            serde_json::Number::from_f64((*f).into())
-                .unwrap_or(serde_json::Number::from_f64(0.0).unwrap()),
-        ),
+        // JSON has no representation for NaN and infinities, so they become null
+        Value::Double(d) => serde_json::Number::from_f64(*d)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        Value::Float(f) => serde_json::Number::from_f64((*f).into())
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
         Value::Enum(_id, desc) => serde_json::Value::String(desc.into()),
+        Value::Fixed(_, f) if options.fixed_as_string => {
+            serde_json::Value::String(fixed_to_string(f))
+        }
         Value::Fixed(_, f) => serde_json::Value::Array(
             f.iter()
                 .map(|b| serde_json::Value::Number((*b).into()))
                 .collect(),
         ),
         Value::Int(i) => serde_json::Value::Number((*i).into()),
+        Value::Long(l) if options.longs_as_strings && l.unsigned_abs() > MAX_SAFE_INTEGER => {
+            serde_json::Value::String(l.to_string())
+        }
         Value::Long(l) => serde_json::Value::Number((*l).into()),
         Value::String(s) => serde_json::Value::String(s.into()),
         Value::Uuid(uuid) => serde_json::Value::String(uuid.to_string()),
-        Value::Date(s) => serde_json::Value::String(
-            jiff::Timestamp::from_second((*s).into())
-                .into_diagnostic()?
-                .to_string(),
-        ),
+        Value::Date(days) => serde_json::Value::String(date_from_days(*days)?.to_string()),
         Value::Decimal(decimal) => {
             serde_json::Value::String(BigInt::from(decimal.clone()).to_string())
         }
-        Value::BigDecimal(big_decimal) => {
-            serde_json::Value::String(big_decimal.as_bigint_and_exponent().0.to_string())
-        }
+        Value::BigDecimal(big_decimal) => serde_json::Value::String(big_decimal.to_string()),
         Value::TimeMillis(ms) => serde_json::Value::String(
             jiff::civil::Time::MIN
                 .saturating_add(Span::new().milliseconds(*ms))
@@ -204,12 +391,12 @@ pub fn to_json(value: &Value) -> Result<serde_json::Value> {
                 .to_string(),
         ),
         Value::TimestampMicros(us) => serde_json::Value::String(
-            jiff::Timestamp::from_millisecond(*us)
+            jiff::Timestamp::from_microsecond(*us)
                 .into_diagnostic()?
                 .to_string(),
         ),
         Value::TimestampNanos(ns) => serde_json::Value::String(
-            jiff::Timestamp::from_millisecond(*ns)
+            jiff::Timestamp::from_nanosecond((*ns).into())
                 .into_diagnostic()?
                 .to_string(),
         ),
@@ -231,14 +418,265 @@ pub fn to_json(value: &Value) -> Result<serde_json::Value> {
                 .to_zoned(TimeZone::try_system().unwrap_or(TimeZone::UTC))
                 .to_string(),
         ),
-        Value::Duration(duration) => serde_json::Value::String(
-            (jiff::Span::new()
-                .months(u32::from(duration.months()))
-                .checked_add(jiff::Span::new().days(u32::from(duration.days())))
-                .into_diagnostic()?
-                .checked_add(jiff::Span::new().milliseconds(u32::from(duration.millis())))
-                .into_diagnostic()?)
-            .to_string(),
+        Value::Duration(duration) => serde_json::Value::String(iso_duration(duration)),
+    })
+}
+
+/// Render a value as JSON5, which unlike JSON has `NaN`, `Infinity` and `-Infinity` literals.
+/// Everything else is rendered as in JSON.
+pub fn to_json5(value: &Value, options: &FormatOptions) -> Result<String> {
+    Ok(match value {
+        Value::Double(d) => json5_number(*d)?,
+        Value::Float(f) => json5_number((*f).into())?,
+        Value::Union(_, value) => to_json5(value, options)?,
+        Value::Array(a) => format!(
+            "[{}]",
+            a.iter()
+                .map(|v| to_json5(v, options))
+                .collect::<Result<Vec<String>>>()?
+                .join(",")
         ),
+        Value::Map(m) => json5_object(
+            m.iter()
+                .map(|(k, v)| Ok((k.to_owned(), to_json5(v, options)?)))
+                .collect::<Result<Vec<(String, String)>>>()?,
+        ),
+        Value::Record(m) => json5_object(
+            m.iter()
+                .map(|(k, v)| Ok((k.to_owned(), to_json5(v, options)?)))
+                .collect::<Result<Vec<(String, String)>>>()?,
+        ),
+        _ => to_json(value, options)?.to_string(),
     })
 }
+
+fn json5_number(n: f64) -> Result<String> {
+    Ok(if n.is_nan() {
+        "NaN".to_owned()
+    } else if n.is_infinite() {
+        if n > 0.0 { "Infinity" } else { "-Infinity" }.to_owned()
+    } else {
+        to_json(&Value::Double(n), &FormatOptions::default())?.to_string()
+    })
+}
+
+/// Render an object from keys and values already rendered as JSON5, sorted by key like JSON
+pub fn json5_object(mut entries: Vec<(String, String)>) -> String {
+    entries.sort();
+    format!(
+        "{{{}}}",
+        entries
+            .into_iter()
+            .map(|(k, v)| format!("{}:{}", serde_json::Value::from(k), v))
+            .collect::<Vec<String>>()
+            .join(",")
+    )
+}
+
+/// Group the integer digits of a number rendered as text, if the options ask for it
+fn group_digits(number: String, options: &FormatOptions) -> String {
+    let Some(locale) = options.group_digits else {
+        return number;
+    };
+    let (thousands, decimal) = locale.separators();
+
+    let (sign, unsigned) = match number.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", number.as_str()),
+    };
+    let (integer, fraction) = match unsigned.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (unsigned, None),
+    };
+    if !integer.bytes().all(|b| b.is_ascii_digit()) {
+        // Not a plain number, e.g. `inf` or `NaN`
+        return number;
+    }
+
+    let mut grouped = sign.to_owned();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(thousands);
+        }
+        grouped.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        grouped.push(decimal);
+        grouped.push_str(fraction);
+    }
+    grouped
+}
+
+/// Round a decimal number to the nearest f64, for display only
+fn decimal_to_float(decimal: &str) -> Result<String> {
+    Ok(decimal.parse::<f64>().into_diagnostic()?.to_string())
+}
+
+/// Decode fixed bytes as UTF-8 text, or as lowercase hex if they aren't valid UTF-8
+fn fixed_to_string(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_owned(),
+        Err(_) => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apache_avro::{Days, Millis, Months, Reader, Schema, Writer};
+
+    #[test]
+    fn test_date() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "event", "fields": [
+                {"name": "day", "type": {"type": "int", "logicalType": "date"}}
+            ]}"#,
+        )
+        .unwrap();
+        let mut writer = Writer::new(&schema, Vec::new());
+        writer
+            .append(Value::Record(vec![("day".to_owned(), Value::Date(18_262))]))
+            .unwrap();
+        let bytes = writer.into_inner().unwrap();
+        let record = Reader::new(&bytes[..]).unwrap().next().unwrap().unwrap();
+        let Value::Record(fields) = record else {
+            panic!("expected a record, got {:?}", record);
+        };
+        let day = &fields[0].1;
+
+        let options = FormatOptions::default();
+        assert_eq!(format_value(day, &options).unwrap(), "2020-01-01");
+        assert_eq!(
+            to_json(day, &options).unwrap(),
+            serde_json::Value::String("2020-01-01".to_owned())
+        );
+        assert_eq!(
+            format_value(&Value::Date(-1), &options).unwrap(),
+            "1969-12-31"
+        );
+        assert!(format_value(&Value::Date(i32::MAX), &options).is_err());
+    }
+
+    #[test]
+    fn test_big_decimal() {
+        let options = FormatOptions::default();
+        for (decimal, text) in [("123.45", "123.45"), ("-0.05", "-0.05"), ("7", "7")] {
+            let value = Value::BigDecimal(decimal.parse().unwrap());
+            assert_eq!(format_value(&value, &options).unwrap(), text);
+            assert_eq!(
+                to_json(&value, &options).unwrap(),
+                serde_json::Value::String(text.to_owned())
+            );
+        }
+
+        let grouped = FormatOptions {
+            group_digits: Some(NumberLocale::De),
+            ..FormatOptions::default()
+        };
+        let value = Value::BigDecimal("1234567.89".parse().unwrap());
+        assert_eq!(format_value(&value, &grouped).unwrap(), "1.234.567,89");
+    }
+
+    #[test]
+    fn test_group_digits() {
+        let cases = [
+            (None, "1234567", "1234567"),
+            (Some(NumberLocale::En), "0", "0"),
+            (Some(NumberLocale::En), "123", "123"),
+            (Some(NumberLocale::En), "1234", "1,234"),
+            (Some(NumberLocale::En), "1234567", "1,234,567"),
+            (Some(NumberLocale::En), "-1234567", "-1,234,567"),
+            (Some(NumberLocale::En), "-123", "-123"),
+            (Some(NumberLocale::En), "1234.5678", "1,234.5678"),
+            (Some(NumberLocale::De), "-1234567.89", "-1.234.567,89"),
+            (
+                Some(NumberLocale::Fr),
+                "1234567.89",
+                "1\u{202f}234\u{202f}567,89",
+            ),
+            (Some(NumberLocale::Ch), "1234567.89", "1'234'567.89"),
+            (Some(NumberLocale::En), "NaN", "NaN"),
+            (Some(NumberLocale::En), "inf", "inf"),
+            (Some(NumberLocale::De), "-inf", "-inf"),
+        ];
+        for (locale, number, grouped) in cases {
+            let options = FormatOptions {
+                group_digits: locale,
+                ..FormatOptions::default()
+            };
+            assert_eq!(
+                group_digits(number.to_owned(), &options),
+                grouped,
+                "{} with {:?}",
+                number,
+                locale
+            );
+        }
+    }
+
+    #[test]
+    fn test_timestamp_precision() {
+        let options = FormatOptions::default();
+        let rendered = |value: Value| {
+            let text = format_value(&value, &options).unwrap();
+            assert_eq!(
+                to_json(&value, &options).unwrap(),
+                serde_json::Value::String(text.clone())
+            );
+            text
+        };
+        assert_eq!(
+            rendered(Value::TimestampMillis(1_600_000_000_123)),
+            "2020-09-13T12:26:40.123Z"
+        );
+        assert_eq!(
+            rendered(Value::TimestampMicros(1_600_000_000_000_000)),
+            "2020-09-13T12:26:40Z"
+        );
+        assert_eq!(
+            rendered(Value::TimestampMicros(1_600_000_000_123_456)),
+            "2020-09-13T12:26:40.123456Z"
+        );
+        assert_eq!(
+            rendered(Value::TimestampNanos(1_600_000_000_123_456_789)),
+            "2020-09-13T12:26:40.123456789Z"
+        );
+        assert_eq!(
+            rendered(Value::TimestampNanos(-1)),
+            "1969-12-31T23:59:59.999999999Z"
+        );
+    }
+
+    #[test]
+    fn test_duration() {
+        let duration = |months, days, millis| {
+            Value::Duration(Duration::new(
+                Months::new(months),
+                Days::new(days),
+                Millis::new(millis),
+            ))
+        };
+        let options = FormatOptions::default();
+
+        // Far more months than a time span can hold, which must not be normalised into days
+        let large = duration(u32::MAX, u32::MAX, u32::MAX);
+        assert_eq!(
+            format_value(&large, &options).unwrap(),
+            "4294967295 months, 4294967295 days, 4294967295 ms"
+        );
+        assert_eq!(
+            to_json(&large, &options).unwrap(),
+            serde_json::Value::String("P4294967295M4294967295DT4294967.295S".to_owned())
+        );
+
+        let small = duration(1, 0, 1500);
+        assert_eq!(
+            format_value(&small, &options).unwrap(),
+            "1 month, 0 days, 1500 ms"
+        );
+        assert_eq!(
+            to_json(&small, &options).unwrap(),
+            serde_json::Value::String("P1M0DT1.5S".to_owned())
+        );
+    }
+}
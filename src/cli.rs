@@ -1,26 +1,605 @@
-use crate::avro_value::AvroValue;
-use apache_avro::{types::Value, Reader};
-use glob::glob;
-use miette::{bail, miette, IntoDiagnostic, Result, WrapErr as _};
-use std::fs::File;
-use std::io::Seek;
-use std::path::PathBuf;
+use crate::avro_value::{format_value, AvroValue, FormatOptions};
+use crate::container::{BlockPosition, ContainerReader, Input};
+use crate::field_path::{self, Segment};
+use crate::filter::RecordFilter;
+use crate::schema;
+use apache_avro::{
+    from_avro_datum,
+    schema::{ArraySchema, MapSchema, NamesRef, RecordSchema, ResolvedSchema},
+    to_avro_datum,
+    types::Value,
+    AvroResult, Codec, Schema,
+};
+use glob::{glob_with, MatchOptions};
+use jiff::{tz::TimeZone, Timestamp};
+use miette::{bail, miette, IntoDiagnostic, Report, Result, WrapErr as _};
+use sha2::{Digest, Sha256};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Cursor, Read, Seek};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+use tracing::{debug, trace, Level};
 
-pub(crate) type AvroData = Vec<Vec<AvroColumnarValue>>;
+/// Extracted values: one row per record, one column per requested field
+pub type AvroData = Vec<Vec<AvroColumnarValue>>;
 
+/// Name of the column holding the keys of an exploded map
+pub const EXPLODED_KEY: &str = "_key";
+/// Name of the column holding the values of an exploded map
+pub const EXPLODED_VALUE: &str = "_value";
+
+/// Name of the column holding the hash of each record
+pub const HASH_COLUMN: &str = "_hash";
+
+/// Name of the column holding the encoded size of each record
+pub const SIZE_COLUMN: &str = "_size_bytes";
+
+/// Name of the column identifying the schema each record was written with
+pub const SCHEMA_COLUMN: &str = "_schema";
+
+/// Default size of the read buffer for each file
+pub const DEFAULT_BUFFER_CAPACITY: usize = 64 * 1024;
+
+#[derive(Debug)]
+enum AvroSource {
+    File(File),
+    /// Contents held in memory, e.g. extracted from an archive or read from stdin
+    Memory(Vec<u8>),
+}
+
+/// An Avro file, or one extracted from an archive
 #[derive(Debug)]
-pub(crate) struct AvroFile {
-    file: File,
+pub struct AvroFile {
+    source: AvroSource,
     path: PathBuf,
+    buffer_capacity: usize,
+    read_mode: ReadMode,
+    /// Number of records read so far, to resume from when the file grows
+    records_read: u64,
+    /// Block of a container file to resume from when it grows
+    position: Option<BlockPosition>,
+    /// Size of the file when it was last read
+    size_read: u64,
+    /// Number of bytes read from the file so far, across all readers
+    bytes_read: Cell<u64>,
+    /// Schema of the datums, for files that are a stream of datums with no container header
+    assumed_schema: Option<Schema>,
+    /// Number of bytes from the start of the file to dump to stderr when it can't be opened
+    dump_bytes: Option<usize>,
+}
+
+/// Records read from an Avro file
+#[allow(clippy::large_enum_variant)]
+pub enum Records<'a> {
+    /// From an object container file, with its header and blocks
+    Container(ContainerReader<'a>),
+    /// From datums written one after the other with no framing, decoded until the end
+    Datums {
+        schema: &'a Schema,
+        read: Box<dyn Input + 'a>,
+        failed: bool,
+    },
+}
+
+impl Records<'_> {
+    pub fn writer_schema(&self) -> &Schema {
+        match self {
+            Self::Container(reader) => reader.writer_schema(),
+            Self::Datums { schema, .. } => schema,
+        }
+    }
+
+    /// Where the next record is, for container files
+    pub fn position(&self) -> Option<BlockPosition> {
+        match self {
+            Self::Container(reader) => Some(reader.position()),
+            Self::Datums { .. } => None,
+        }
+    }
+}
+
+impl Iterator for Records<'_> {
+    type Item = AvroResult<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Container(reader) => reader.next(),
+            Self::Datums {
+                schema,
+                read,
+                failed,
+            } => {
+                // Nothing can be decoded past an error, as where the next datum starts is lost
+                if *failed || read.fill_buf().is_ok_and(|buf| buf.is_empty()) {
+                    return None;
+                }
+                let datum = from_avro_datum(schema, read, None);
+                *failed = datum.is_err();
+                Some(datum)
+            }
+        }
+    }
 }
 
+/// A set of Avro files read as one, in order
 #[derive(Debug)]
-pub(crate) struct CliService {
+pub struct CliService {
     files: Vec<AvroFile>,
+    /// Keys of the records kept so far with `dedupe_by`, so records appended later are
+    /// checked against them too
+    seen_keys: HashSet<Vec<String>>,
+}
+
+/// Order to read files in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum FileOrder {
+    /// Lexically by path
+    #[default]
+    Name,
+    /// Oldest modified first
+    Mtime,
+    /// Smallest first
+    Size,
+}
+
+/// How the records of input files are laid out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputFormat {
+    /// Avro object container files, with the schema in their header
+    #[default]
+    Container,
+    /// Datums written one after the other with no header or framing, of an assumed schema
+    DatumStream,
+}
+
+/// How input files are read from disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReadMode {
+    /// Read through a buffer as records are decoded
+    #[default]
+    Stream,
+    /// Read each whole file into memory before decoding it
+    Whole,
+}
+
+/// How to order field names gathered across files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum FieldOrder {
+    /// In the order the writer schemas declare them
+    #[default]
+    Schema,
+    /// In the order they first have a value other than null, reading records across files
+    /// until every field had one. Fields that never do come last, in schema order
+    FirstSeen,
+}
+
+/// Options controlling which records and values `get_fields` extracts
+#[derive(Debug, Clone, Default)]
+pub struct GetOptions {
+    /// Number of rows to take
+    pub take: Option<u32>,
+    /// Match field names regardless of case
+    pub fields_ci: bool,
+    /// Warn when reading more than this many bytes without a `take` limit
+    pub warn_over_bytes: Option<u64>,
+    /// Check enum values against the symbols declared in the schema
+    pub enum_validate: bool,
+    /// Fail instead of warning when a field decodes as the base type of its logical type
+    pub fail_on_schema_warning: bool,
+    /// Show which branch each union holds, as a record with a single field named after it
+    pub annotate_unions: bool,
+    /// Keep decimals as decoded, as their unscaled integer, instead of giving them the scale
+    /// of their schema. Values written back as Avro need this
+    pub raw_decimals: bool,
+    /// Skip records that can't be decoded or fail validation, instead of stopping. Records
+    /// that can't be decoded are skipped along with the rest of their block
+    pub skip_errors: bool,
+    /// Skip up to this many records that can't be decoded or fail validation, then stop.
+    /// Each block that can't be decoded counts once
+    pub abort_after_errors: Option<u64>,
+    /// Only extract records whose index across all files is in this range
+    pub where_index: Option<IndexRange>,
+    /// Output one row per entry of this map or array field, in `_key` and `_value` columns
+    pub explode_map: Option<String>,
+    /// Levels of nested maps and arrays to explode, each adding a key column. 0 counts as 1
+    pub explode_depth: usize,
+    /// Keep at most this many rows exploded from a single record, warning when there are more
+    pub max_exploded_rows: Option<usize>,
+    /// Treat strings containing the U+FFFD replacement character as decoding errors
+    pub strict_utf8: bool,
+    /// Add a `_hash` column with a SHA-256 hash of each whole record
+    pub hash: bool,
+    /// Add a `_size_bytes` column with the length of each record's Avro encoding
+    pub with_size: bool,
+    /// Add a leading `_schema` column with the name of the schema each record was written with
+    pub with_schema_id: bool,
+    /// Only extract records where a union field holds this branch
+    pub union_branch: Option<UnionBranch>,
+    /// Find which field of a record that can't be decoded is at fault, and where
+    pub explain_errors: bool,
+    /// Only extract records where a timestamp field is in this range
+    pub time_range: Option<TimeRange>,
+    /// Only extract records this expression is true for
+    pub record_filter: Option<RecordFilter>,
+    /// Only extract the first record for each combination of values of these fields
+    pub dedupe_by: Vec<String>,
+    /// Skip records that have none of the requested fields
+    pub drop_empty: bool,
+    /// Values shown instead of null or N/A for some fields
+    pub replace_null: Vec<NullReplacement>,
+    /// Types to convert some fields to, so that every value renders the same way
+    pub coerce: Vec<FieldCoercion>,
+}
+
+impl GetOptions {
+    /// Names of the columns extracted for these fields, once maps are exploded
+    pub fn columns(&self, fields_to_get: &[String]) -> Vec<String> {
+        self.with_schema_id
+            .then(|| SCHEMA_COLUMN.to_owned())
+            .into_iter()
+            .chain(fields_to_get.iter().flat_map(|field| {
+                if self.explode_map.as_ref() == Some(field) {
+                    (1..=self.explode_depth.max(1))
+                        .map(exploded_key)
+                        .chain([EXPLODED_VALUE.to_owned()])
+                        .collect()
+                } else {
+                    vec![field.to_owned()]
+                }
+            }))
+            .chain(self.hash.then(|| HASH_COLUMN.to_owned()))
+            .chain(self.with_size.then(|| SIZE_COLUMN.to_owned()))
+            .collect()
+    }
+
+    fn field_matches(&self, name: &str, wanted: &str) -> bool {
+        if self.fields_ci {
+            name.eq_ignore_ascii_case(wanted)
+        } else {
+            name == wanted
+        }
+    }
+}
+
+/// A range of record indices, written `start..end`, `start..=end` or `index`.
+/// Either bound of `..` may be omitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexRange {
+    start: u64,
+    /// Exclusive end
+    end: Option<u64>,
+}
+
+impl IndexRange {
+    pub fn contains(&self, index: u64) -> bool {
+        index >= self.start && !self.is_past(index)
+    }
+
+    /// Whether this index and all those after it are outside the range
+    pub fn is_past(&self, index: u64) -> bool {
+        self.end.is_some_and(|end| index >= end)
+    }
+}
+
+impl FromStr for IndexRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<u64>()
+                .map_err(|e| format!("invalid index `{}`: {}", n, e))
+        };
+
+        if let Some((start, end)) = s.split_once("..=") {
+            let start = if start.is_empty() { 0 } else { parse(start)? };
+            // An inclusive end at the largest index leaves no index out
+            Ok(IndexRange {
+                start,
+                end: parse(end)?.checked_add(1),
+            })
+        } else if let Some((start, end)) = s.split_once("..") {
+            let start = if start.is_empty() { 0 } else { parse(start)? };
+            let end = if end.is_empty() {
+                None
+            } else {
+                Some(parse(end)?)
+            };
+            Ok(IndexRange { start, end })
+        } else {
+            let index = parse(s)?;
+            Ok(IndexRange {
+                start: index,
+                end: index.checked_add(1),
+            })
+        }
+    }
+}
+
+/// A union field and one of its branches, written `field=branch`.
+/// The branch is given by its index in the union, or by its type name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnionBranch {
+    field: String,
+    branch: String,
+}
+
+impl UnionBranch {
+    /// Find the index of the branch in the union field of a record schema.
+    /// None when the field isn't a union with that branch.
+    fn index_in(&self, schema: &Schema) -> Result<Option<u32>> {
+        if let Ok(index) = self.branch.parse() {
+            return Ok(Some(index));
+        }
+
+        let resolved = ResolvedSchema::try_from(schema).into_diagnostic()?;
+        let names = resolved.get_names();
+        let Schema::Record(record) = schema::resolve(schema, names) else {
+            return Ok(None);
+        };
+        let Some(field) = record.lookup.get(&self.field).map(|&i| &record.fields[i]) else {
+            return Ok(None);
+        };
+        let Schema::Union(union) = schema::resolve(&field.schema, names) else {
+            return Ok(None);
+        };
+        Ok(union
+            .variants()
+            .iter()
+            .position(|variant| schema::type_name(variant) == self.branch)
+            .map(|index| index as u32))
+    }
+
+    fn selects(&self, fields: &[(String, Value)], index: Option<u32>) -> bool {
+        matches!(
+            fields.iter().find(|(name, _)| *name == self.field),
+            Some((_, Value::Union(branch, _))) if Some(*branch) == index
+        )
+    }
+}
+
+impl FromStr for UnionBranch {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((field, branch)) if !field.is_empty() && !branch.is_empty() => Ok(UnionBranch {
+                field: field.to_owned(),
+                branch: branch.to_owned(),
+            }),
+            _ => Err(format!("expected `field=branch`, got `{}`", s)),
+        }
+    }
+}
+
+/// A value to show instead of null or N/A for a field, written `field=value`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NullReplacement {
+    field: String,
+    value: String,
+}
+
+impl NullReplacement {
+    /// The replacement as a value of the field's type when it parses as one, or else a string
+    fn typed(&self, schema: Option<&Schema>) -> Value {
+        let typed = match schema {
+            Some(Schema::Union(union)) => union
+                .variants()
+                .iter()
+                .map(|variant| self.typed(Some(variant)))
+                .find(|value| !matches!(value, Value::String(_))),
+            Some(Schema::Boolean) => self.value.parse().ok().map(Value::Boolean),
+            Some(Schema::Int) => self.value.parse().ok().map(Value::Int),
+            Some(Schema::Long) => self.value.parse().ok().map(Value::Long),
+            Some(Schema::Float) => self.value.parse().ok().map(Value::Float),
+            Some(Schema::Double) => self.value.parse().ok().map(Value::Double),
+            _ => None,
+        };
+        typed.unwrap_or_else(|| Value::String(self.value.clone()))
+    }
+}
+
+impl FromStr for NullReplacement {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((field, value)) if !field.is_empty() => Ok(NullReplacement {
+                field: field.to_owned(),
+                value: value.to_owned(),
+            }),
+            _ => Err(format!("expected `field=value`, got `{}`", s)),
+        }
+    }
+}
+
+/// A type to convert the values of a field to, written `field=type` with a type of `long`,
+/// `double`, `boolean` or `string`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldCoercion {
+    field: String,
+    to: CoercedType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CoercedType {
+    Long,
+    Double,
+    Boolean,
+    String,
+}
+
+impl FieldCoercion {
+    /// The value as the target type, or None when it can't be converted. Nulls stay null.
+    ///
+    /// Numbers convert to each other when no precision is lost, and decimals to the nearest
+    /// double, booleans to and from 0 and 1, strings by parsing them, and anything to a string
+    /// as it is shown in tables.
+    fn coerce(&self, value: &Value) -> Option<Value> {
+        let value = match value {
+            Value::Union(_, value) => value,
+            value => value,
+        };
+        if *value == Value::Null {
+            return Some(Value::Null);
+        }
+        match self.to {
+            CoercedType::Long => match value {
+                Value::Int(i) | Value::Date(i) | Value::TimeMillis(i) => {
+                    Some(Value::Long((*i).into()))
+                }
+                Value::Long(l)
+                | Value::TimeMicros(l)
+                | Value::TimestampMillis(l)
+                | Value::TimestampMicros(l)
+                | Value::TimestampNanos(l)
+                | Value::LocalTimestampMillis(l)
+                | Value::LocalTimestampMicros(l)
+                | Value::LocalTimestampNanos(l) => Some(Value::Long(*l)),
+                Value::Float(f) => whole(f64::from(*f)).map(Value::Long),
+                Value::Double(d) => whole(*d).map(Value::Long),
+                Value::Boolean(b) => Some(Value::Long(i64::from(*b))),
+                Value::String(s) => s.trim().parse().ok().map(Value::Long),
+                _ => None,
+            },
+            CoercedType::Double => match value {
+                Value::Int(i) => Some(Value::Double((*i).into())),
+                Value::Long(l) => Some(Value::Double(*l as f64)),
+                Value::Float(f) => Some(Value::Double((*f).into())),
+                Value::Double(d) => Some(Value::Double(*d)),
+                Value::String(s) => s.trim().parse().ok().map(Value::Double),
+                Value::Decimal(_) | Value::BigDecimal(_) => {
+                    let options = FormatOptions {
+                        decimal_as_float: true,
+                        ..FormatOptions::default()
+                    };
+                    let text = format_value(value, &options).ok()?;
+                    text.parse().ok().map(Value::Double)
+                }
+                _ => None,
+            },
+            CoercedType::Boolean => match value {
+                Value::Boolean(b) => Some(Value::Boolean(*b)),
+                Value::Int(0) | Value::Long(0) => Some(Value::Boolean(false)),
+                Value::Int(1) | Value::Long(1) => Some(Value::Boolean(true)),
+                Value::String(s) => s.trim().parse().ok().map(Value::Boolean),
+                _ => None,
+            },
+            CoercedType::String => format_value(value, &FormatOptions::default())
+                .ok()
+                .map(Value::String),
+        }
+    }
+}
+
+/// A float as a long, if it is a whole number in range
+fn whole(number: f64) -> Option<i64> {
+    (number.fract() == 0.0 && number >= i64::MIN as f64 && number < i64::MAX as f64)
+        .then_some(number as i64)
+}
+
+impl FromStr for FieldCoercion {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let Some((field, to)) = s.split_once('=').filter(|(field, _)| !field.is_empty()) else {
+            return Err(format!("expected `field=type`, got `{}`", s));
+        };
+        let to = match to {
+            "long" => CoercedType::Long,
+            "double" => CoercedType::Double,
+            "boolean" => CoercedType::Boolean,
+            "string" => CoercedType::String,
+            _ => {
+                return Err(format!(
+                    "unknown type `{}`, expected long, double, boolean or string",
+                    to
+                ))
+            }
+        };
+        Ok(FieldCoercion {
+            field: field.to_owned(),
+            to,
+        })
+    }
+}
+
+/// Bounds on the value of a timestamp or date field: from `since` included to `until` excluded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeRange {
+    pub field: String,
+    pub since: Option<Timestamp>,
+    pub until: Option<Timestamp>,
+}
+
+impl TimeRange {
+    /// Parse a bound: an RFC 3339 timestamp, or a date or date and time taken as UTC
+    pub fn parse_bound(s: &str) -> std::result::Result<Timestamp, String> {
+        if let Ok(timestamp) = s.parse::<Timestamp>() {
+            return Ok(timestamp);
+        }
+        s.parse::<jiff::civil::DateTime>()
+            .and_then(|datetime| datetime.to_zoned(TimeZone::UTC))
+            .map(|zoned| zoned.timestamp())
+            .map_err(|e| format!("invalid timestamp `{}`: {}", s, e))
+    }
+
+    /// Whether the field of a record is in range. Records without the field, or where
+    /// it is null, are not.
+    fn contains(&self, fields: &[(String, Value)], options: &GetOptions) -> Result<bool> {
+        let Some((_, value)) = fields
+            .iter()
+            .find(|(name, _)| options.field_matches(name, &self.field))
+        else {
+            return Ok(false);
+        };
+        let Some(timestamp) = timestamp_of(value)
+            .wrap_err_with(|| format!("Field `{}` can't be filtered by time", self.field))?
+        else {
+            return Ok(false);
+        };
+        Ok(self.since.is_none_or(|since| timestamp >= since)
+            && self.until.is_none_or(|until| timestamp < until))
+    }
+}
+
+/// The instant a timestamp or date value stands for, or None for null.
+/// Local timestamps and dates are taken as UTC.
+fn timestamp_of(value: &Value) -> Result<Option<Timestamp>> {
+    Ok(Some(match value {
+        Value::Null => return Ok(None),
+        Value::Union(_, value) => return timestamp_of(value),
+        Value::TimestampMillis(ms) | Value::LocalTimestampMillis(ms) => {
+            Timestamp::from_millisecond(*ms).into_diagnostic()?
+        }
+        Value::TimestampMicros(us) | Value::LocalTimestampMicros(us) => {
+            Timestamp::from_microsecond(*us).into_diagnostic()?
+        }
+        Value::TimestampNanos(ns) | Value::LocalTimestampNanos(ns) => {
+            Timestamp::from_nanosecond((*ns).into()).into_diagnostic()?
+        }
+        Value::Date(days) => jiff::civil::Date::new(1970, 1, 1)
+            .into_diagnostic()?
+            .checked_add(jiff::Span::new().days(*days))
+            .into_diagnostic()?
+            .to_zoned(TimeZone::UTC)
+            .into_diagnostic()?
+            .timestamp(),
+        other => bail!(
+            "Expected a timestamp or date, got {}",
+            AvroValue::from(other.clone())
+        ),
+    }))
 }
 
+/// The value of a field in a record, with the name it was requested by
 #[derive(Debug, Clone)]
-pub(crate) struct AvroColumnarValue {
+pub struct AvroColumnarValue {
     name: String,
     value: AvroValue,
 }
@@ -39,141 +618,2217 @@ impl AvroColumnarValue {
     }
 }
 
+impl AvroFile {
+    fn new(source: AvroSource, path: PathBuf) -> Self {
+        AvroFile {
+            source,
+            path,
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+            read_mode: ReadMode::Stream,
+            records_read: 0,
+            position: None,
+            size_read: 0,
+            bytes_read: Cell::new(0),
+            assumed_schema: None,
+            dump_bytes: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn size(&self) -> Result<u64> {
+        Ok(match &self.source {
+            AvroSource::File(file) => file.metadata().into_diagnostic()?.len(),
+            AvroSource::Memory(bytes) => bytes.len() as u64,
+        })
+    }
+
+    /// The whole content of the file
+    pub fn read_all(&mut self) -> Result<Vec<u8>> {
+        match &self.source {
+            AvroSource::File(file) => read_whole(file, &self.path),
+            AvroSource::Memory(bytes) => Ok(bytes.clone()),
+        }
+    }
+
+    /// Last modification time, if known. Files extracted from archives don't have one
+    pub fn modified(&self) -> Result<Option<SystemTime>> {
+        Ok(match &self.source {
+            AvroSource::File(file) => Some(
+                file.metadata()
+                    .into_diagnostic()?
+                    .modified()
+                    .into_diagnostic()?,
+            ),
+            AvroSource::Memory(_) => None,
+        })
+    }
+
+    /// Rewinds the file and opens an Avro reader over it
+    pub fn reader(&mut self) -> Result<Records<'_>> {
+        let count = &self.bytes_read;
+        let mut read: Box<dyn Input + '_> = match &mut self.source {
+            AvroSource::File(file) => {
+                file.seek(std::io::SeekFrom::Start(0)).into_diagnostic()?;
+                match self.read_mode {
+                    ReadMode::Stream => Box::new(BufReader::with_capacity(
+                        self.buffer_capacity,
+                        CountingReader {
+                            inner: &*file,
+                            count,
+                        },
+                    )),
+                    ReadMode::Whole => {
+                        let bytes = read_whole(file, &self.path)?;
+                        count.set(count.get() + bytes.len() as u64);
+                        Box::new(Cursor::new(bytes))
+                    }
+                }
+            }
+            AvroSource::Memory(bytes) => Box::new(BufReader::new(CountingReader {
+                inner: Cursor::new(&bytes[..]),
+                count,
+            })),
+        };
+        if tracing::enabled!(Level::DEBUG) && self.assumed_schema.is_none() {
+            // The header is usually small enough to be in the first buffer
+            match read.fill_buf().ok().map(crate::container::Header::parse) {
+                Some(Ok(header)) => debug!(
+                    "Opened {} with the {:?} codec",
+                    self.path.display(),
+                    header.codec
+                ),
+                _ => debug!("Opened {}", self.path.display()),
+            }
+        } else {
+            trace!("Opened {}", self.path.display());
+        }
+        // Only as much as fits in the read buffer, which is plenty to recognise a file
+        let start = match self.dump_bytes {
+            Some(len) => read
+                .fill_buf()
+                .ok()
+                .map(|bytes| bytes[..len.min(bytes.len())].to_vec()),
+            None => None,
+        };
+        match &self.assumed_schema {
+            Some(schema) => Ok(Records::Datums {
+                schema,
+                read,
+                failed: false,
+            }),
+            None => ContainerReader::new(read)
+                .map(Records::Container)
+                .inspect_err(|_| {
+                    if let Some(start) = &start {
+                        eprint!(
+                            "First {} bytes of {}:\n{}",
+                            start.len(),
+                            self.path.display(),
+                            crate::container::hex_dump(start)
+                        );
+                    }
+                })
+                .wrap_err_with(|| format!("Could not read Avro file {}", self.path.display())),
+        }
+    }
+}
+
 impl CliService {
-    /// Creates an `Avro` as a union of all avros in the received paths
+    /// Creates an `Avro` as a union of all avros in the received paths, sorted by name
     ///
     /// # Arguments
     ///
-    /// * `path` - A glob to match against Avro files to load
+    /// * `path` - A glob to match against Avro files to load. With the `archive` feature,
+    ///   matched zip archives are expanded into the `.avro` files they contain
     pub fn from(path: String) -> Result<Self> {
-        let mut paths: Vec<PathBuf> = Vec::new();
-        for entry in glob(&path)
-            .into_diagnostic()
-            .wrap_err("Failed to read glob pattern")?
+        Self::from_glob(path, true)
+    }
+
+    /// Like `from`, choosing whether the glob follows symbolic links.
+    ///
+    /// Files reached through several paths, e.g. a symlink and its target, are only
+    /// read once, under the first path matched.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A glob to match against Avro files to load, or `-` to read from stdin
+    /// * `follow_symlinks` - When false, matches that are symlinks or that go through a
+    ///   symlinked directory below the literal start of the glob are left out
+    pub fn from_glob(path: String, follow_symlinks: bool) -> Result<Self> {
+        if path == "-" {
+            return Self::from_reader(std::io::stdin().lock());
+        }
+        let mut files: Vec<AvroFile> = Vec::new();
+        for path in expand_glob(&path, follow_symlinks)?
+            .into_iter()
+            .filter(|matched| matched.skipped.is_none())
+            .map(|matched| matched.path)
         {
-            match entry {
-                Ok(p) => paths.push(p),
-                Err(e) => bail!("{:?}", e),
+            #[allow(unused_mut)]
+            let mut file = File::open(&path)
+                .into_diagnostic()
+                .wrap_err("Could not open file")?;
+
+            #[cfg(feature = "archive")]
+            if crate::archive::is_zip(&path, &mut file)? {
+                for (path, bytes) in crate::archive::avro_entries(&path, file)? {
+                    debug!("Found {} in an archive", path.display());
+                    files.push(AvroFile::new(AvroSource::Memory(bytes), path));
+                }
+                continue;
             }
+
+            debug!("Found {}", path.display());
+            files.push(AvroFile::new(AvroSource::File(file), path));
         }
 
-        if paths.len() == 0 {
+        if files.is_empty() {
             bail!("No files found");
         }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
 
-        let mut files: Vec<AvroFile> = Vec::new();
-        for path in paths {
-            let file = File::open(&path)
-                .into_diagnostic()
-                .wrap_err("Could not open file")?;
-            files.push(AvroFile { file, path });
+        Ok(CliService {
+            files,
+            seen_keys: HashSet::new(),
+        })
+    }
+
+    /// Read a single Avro file into memory from something that can only be read once, like
+    /// stdin, so that it can still be read again from the start. The file is named `-`
+    pub fn from_reader(mut read: impl Read) -> Result<Self> {
+        let mut bytes = Vec::new();
+        read.read_to_end(&mut bytes)
+            .into_diagnostic()
+            .wrap_err("Could not read the input")?;
+        debug!("Read {} bytes from the input", bytes.len());
+        Ok(CliService {
+            files: vec![AvroFile::new(AvroSource::Memory(bytes), PathBuf::from("-"))],
+            seen_keys: HashSet::new(),
+        })
+    }
+
+    /// Set the size of the read buffer used for each file
+    pub fn set_buffer_capacity(&mut self, capacity: usize) {
+        for file in &mut self.files {
+            file.buffer_capacity = capacity;
         }
+    }
+
+    /// Set how files are read from disk. Files from archives are already in memory
+    pub fn set_read_mode(&mut self, mode: ReadMode) {
+        for file in &mut self.files {
+            file.read_mode = mode;
+        }
+    }
+
+    /// When a file can't be opened as Avro, print a hex dump of its first bytes to stderr,
+    /// to tell what it is instead
+    pub fn dump_unreadable(&mut self, bytes: usize) {
+        for file in &mut self.files {
+            file.dump_bytes = Some(bytes);
+        }
+    }
+
+    /// Change the order the files are read in. Files that compare equal keep their order
+    pub fn sort_files(&mut self, order: FileOrder) -> Result<()> {
+        match order {
+            FileOrder::Name => self.files.sort_by(|a, b| a.path.cmp(&b.path)),
+            FileOrder::Mtime => sort_files_by_key(&mut self.files, AvroFile::modified)?,
+            FileOrder::Size => sort_files_by_key(&mut self.files, AvroFile::size)?,
+        }
+        Ok(())
+    }
+
+    /// Read the files as streams of datums of this schema, with no container header
+    pub fn assume_schema(&mut self, schema: Schema) {
+        for file in &mut self.files {
+            file.assumed_schema = Some(schema.clone());
+        }
+    }
 
-        Ok(CliService { files })
+    pub fn files(&self) -> &[AvroFile] {
+        &self.files
+    }
+
+    pub fn files_mut(&mut self) -> &mut [AvroFile] {
+        &mut self.files
+    }
+
+    /// Number of records decoded from the files by the last read
+    pub fn records_read(&self) -> u64 {
+        self.files.iter().map(|file| file.records_read).sum()
+    }
+
+    /// Number of bytes read from the files so far
+    pub fn bytes_read(&self) -> u64 {
+        self.files.iter().map(|file| file.bytes_read.get()).sum()
+    }
+
+    /// Get the writer schema of the first file
+    pub fn writer_schema(&mut self) -> Result<Schema> {
+        let reader = self.files[0].reader()?;
+        Ok(reader.writer_schema().clone())
     }
 
     /// Get all the names of the columns.
-    /// Relies on the first record
+    /// Read from the writer schema of the first file, so files without records still have columns
     pub fn get_all_field_names(&mut self) -> Result<Vec<String>> {
-        let first_file = &mut self.files[0];
-        first_file
-            .file
-            .seek(std::io::SeekFrom::Start(0))
-            .into_diagnostic()?;
-        let mut reader = Reader::new(&first_file.file)
-            .into_diagnostic()
-            .wrap_err_with(|| format!("Could not read Avro file {}", first_file.path.display()))?;
-        Ok(
-            if let Ok(Value::Record(fields)) = reader.next().ok_or(miette!(
-                "Avro must have at least one record row to infer schema"
-            ))? {
-                fields
-                    .iter()
-                    .map(|(f, _)| f.to_owned())
-                    .collect::<Vec<String>>()
-            } else {
-                Vec::new()
-            },
-        )
+        Ok(record_field_names(&self.writer_schema()?))
     }
 
-    /// Get all columns and values
-    ///
-    /// # Arguments
-    /// * `fields_to_get` - Names of the columns to retrieve
-    /// * `take` - Number of rows to take
-    pub fn get_fields(
-        &mut self,
-        fields_to_get: &[String],
-        take: Option<u32>,
-    ) -> Result<Vec<Vec<AvroColumnarValue>>> {
-        let mut extracted_fields = Vec::new();
+    /// Get the names of the columns of every file, each name once.
+    /// With `FieldOrder::Schema`, names are ordered by the first file that has them, then as
+    /// declared within a file. With `FieldOrder::FirstSeen`, by the first record they have a
+    /// value in.
+    pub fn get_field_names_across_files(&mut self, order: FieldOrder) -> Result<Vec<String>> {
+        let mut names: Vec<String> = Vec::new();
+        // Fields yet to have a value, for `FirstSeen`
+        let mut unseen: Vec<String> = Vec::new();
         for file in &mut self.files {
-            file.file
-                .seek(std::io::SeekFrom::Start(0))
-                .into_diagnostic()?;
-            let reader = Reader::new(&file.file)
-                .into_diagnostic()
-                .wrap_err_with(|| format!("Could not read Avro file {}", file.path.display()))?;
+            let path = file.path().to_owned();
+            let reader = file.reader()?;
+            for name in record_field_names(reader.writer_schema()) {
+                if !names.contains(&name) && !unseen.contains(&name) {
+                    unseen.push(name);
+                }
+            }
+            if order == FieldOrder::Schema {
+                names.append(&mut unseen);
+                continue;
+            }
 
             for (i, row) in reader.enumerate() {
-                if extracted_fields.len() as u32 >= take.unwrap_or(u32::max_value()) {
+                if unseen.is_empty() {
                     break;
                 }
-
                 let row = row
                     .into_diagnostic()
-                    .wrap_err_with(|| format!("Could not parse row {} from the Avro", i))?;
-                if let Value::Record(fields) = row {
-                    let mut extracted_fields_for_row = Vec::new();
-                    for field_name in fields_to_get {
-                        let field_value_to_insert = match fields
-                            .iter()
-                            .find(|(n, _)| n == field_name)
-                        {
-                            Some((field_name, field_value)) => {
-                                let v = field_value.clone();
-                                AvroColumnarValue::from(field_name.to_owned(), AvroValue::from(v))
-                            }
-                            None => AvroColumnarValue::from(field_name.to_owned(), AvroValue::na()),
-                        };
-                        extracted_fields_for_row.push(field_value_to_insert);
+                    .wrap_err_with(|| format!("Could not parse row {} of {}", i, path.display()))?;
+                let Value::Record(fields) = row else {
+                    continue;
+                };
+                for (name, value) in fields {
+                    if is_null_or_na(&AvroValue::from(value)) {
+                        continue;
+                    }
+                    if let Some(position) = unseen.iter().position(|unseen| *unseen == name) {
+                        names.push(unseen.remove(position));
                     }
-                    extracted_fields.push(extracted_fields_for_row);
                 }
             }
         }
+        names.append(&mut unseen);
+        Ok(names)
+    }
 
-        Ok(extracted_fields)
+    /// Find which of these fields each file's schema doesn't have, without reading records.
+    /// Fields are top-level names or dotted paths to nested fields, like `address.city`.
+    /// Only files missing some fields are returned.
+    pub fn missing_fields(
+        &mut self,
+        fields: &[String],
+        ignore_case: bool,
+    ) -> Result<Vec<(PathBuf, Vec<String>)>> {
+        let mut missing_by_file = Vec::new();
+        for file in &mut self.files {
+            let path = file.path().to_owned();
+            let reader = file.reader()?;
+            let schema = reader.writer_schema();
+            let mut known = record_field_names(schema);
+            known.extend(schema::leaf_paths(schema)?);
+            let missing: Vec<String> = fields
+                .iter()
+                .filter(|field| {
+                    !known.iter().any(|name| {
+                        if ignore_case {
+                            name.eq_ignore_ascii_case(field)
+                        } else {
+                            name == *field
+                        }
+                    })
+                })
+                .cloned()
+                .collect();
+            if !missing.is_empty() {
+                missing_by_file.push((path, missing));
+            }
+        }
+        Ok(missing_by_file)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::path::Path;
+    /// Check that records appended to the files can be read with `get_appended_fields`:
+    /// container files must use the null or deflate codec
+    pub fn check_watchable(&mut self) -> Result<()> {
+        for file in &mut self.files {
+            let path = file.path().to_owned();
+            if let Records::Container(reader) = file.reader()? {
+                let codec = reader.header()?.codec;
+                if !matches!(codec, Codec::Null | Codec::Deflate) {
+                    bail!(
+                        "{} uses the {:?} codec, only files using the null or deflate codec can be watched",
+                        path.display(),
+                        codec
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
 
-    #[test]
-    fn test_get_all_field_names() {
-        println!("asdas");
-        let path_to_test_avro = Path::new("./test_assets/bttf.avro")
-            .to_str()
-            .unwrap()
-            .to_owned();
-        let mut cli = CliService::from(path_to_test_avro).unwrap();
-        let field_names = cli.get_all_field_names().unwrap();
-        assert_eq!(field_names, vec!["firstName", "lastName", "age"]);
+    /// Count the records of each file. Records are decoded but not extracted, so this
+    /// is quicker than getting any field, and works whatever the type of the records.
+    pub fn count_records(&mut self) -> Result<Vec<(PathBuf, u64)>> {
+        let mut counts = Vec::with_capacity(self.files.len());
+        for file in &mut self.files {
+            let path = file.path().to_owned();
+            let mut count = 0;
+            for row in file.reader()? {
+                row.into_diagnostic().wrap_err_with(|| {
+                    format!("Could not parse row {} of {}", count, path.display())
+                })?;
+                count += 1;
+            }
+            counts.push((path, count));
+        }
+        Ok(counts)
     }
 
-    #[test]
-    fn test_get_fields() {
-        println!("asdas");
-        let path_to_test_avro = Path::new("./test_assets/bttf.avro")
-            .to_str()
-            .unwrap()
-            .to_owned();
-        let _cli = CliService::from(path_to_test_avro).unwrap();
-        // let field_names = cli.get_fields(vec!["firstName", "age"], None);
-        // assert_eq!(field_names, vec!["firstName", "lastName", "age"]);
+    /// Group the files by the SHA-256 fingerprint of their schema's canonical form, without
+    /// reading records. Clusters are in the order their first file is read in.
+    pub fn schema_clusters(&mut self) -> Result<Vec<(String, Vec<PathBuf>)>> {
+        let mut clusters: Vec<(String, Vec<PathBuf>)> = Vec::new();
+        for file in &mut self.files {
+            let path = file.path().to_owned();
+            let fingerprint = file
+                .reader()?
+                .writer_schema()
+                .fingerprint::<Sha256>()
+                .to_string();
+            match clusters.iter_mut().find(|(known, _)| *known == fingerprint) {
+                Some((_, paths)) => paths.push(path),
+                None => clusters.push((fingerprint, vec![path])),
+            }
+        }
+        Ok(clusters)
+    }
+
+    /// Resolve field names against the columns of every file, ignoring case.
+    /// When several columns match a name, the first one in schema order wins.
+    /// Names that match no column are kept as given.
+    pub fn resolve_field_names_ci(&mut self, fields_to_get: Vec<String>) -> Result<Vec<String>> {
+        let all_field_names = self.get_field_names_across_files(FieldOrder::Schema)?;
+        Ok(fields_to_get
+            .into_iter()
+            .map(|wanted| {
+                let name = match field_path::parse(&wanted).first() {
+                    Some(&Segment::Field(name)) => name,
+                    _ => wanted.as_str(),
+                };
+                let path = &wanted[name.len()..];
+                let mut matching = all_field_names
+                    .iter()
+                    .filter(|field| field.eq_ignore_ascii_case(name));
+                match matching.next() {
+                    None => wanted,
+                    Some(first) => {
+                        let others: Vec<&String> = matching.collect();
+                        if !others.is_empty() {
+                            warn(&miette!(
+                                "field `{}` matches several fields ignoring case, using `{}` over {:?}",
+                                name,
+                                first,
+                                others
+                            ));
+                        }
+                        format!("{}{}", first, path)
+                    }
+                }
+            })
+            .collect())
+    }
+
+    /// Get all columns and values
+    ///
+    /// Fields are looked up by name in the schema of each file, so files that order their
+    /// fields differently still line up. Fields a file doesn't have are `N/A`.
+    ///
+    /// # Arguments
+    /// * `fields_to_get` - Names of the columns to retrieve. A name may be a path into nested
+    ///   records, maps and arrays, like `address.city` or `items[2].name`, with negative
+    ///   indices counting from the end of arrays, like `tags[-1]` for the last element
+    /// * `options` - Which rows to take and how to match field names
+    pub fn get_fields(
+        &mut self,
+        fields_to_get: &[String],
+        options: &GetOptions,
+    ) -> Result<Vec<Vec<AvroColumnarValue>>> {
+        self.read_fields(0..self.files.len(), fields_to_get, options, false)
+    }
+
+    /// Get the columns and values of a single file, by its position in `files`.
+    /// Options that limit rows, like `take` and `where_index`, apply to that file alone.
+    pub fn get_file_fields(
+        &mut self,
+        file: usize,
+        fields_to_get: &[String],
+        options: &GetOptions,
+    ) -> Result<Vec<Vec<AvroColumnarValue>>> {
+        self.read_fields(file..file + 1, fields_to_get, options, false)
+    }
+
+    /// Get the columns and values of records appended to the files since they were last read.
+    /// An incomplete record at the end of a file is left for a later call.
+    pub fn get_appended_fields(
+        &mut self,
+        fields_to_get: &[String],
+        options: &GetOptions,
+    ) -> Result<Vec<Vec<AvroColumnarValue>>> {
+        self.read_fields(0..self.files.len(), fields_to_get, options, true)
+    }
+
+    fn read_fields(
+        &mut self,
+        files: Range<usize>,
+        fields_to_get: &[String],
+        options: &GetOptions,
+        appended_only: bool,
+    ) -> Result<Vec<Vec<AvroColumnarValue>>> {
+        // Only a full read is worth warning about: `where_index` seeks straight to its records
+        // and appended records are read a little at a time
+        let unlimited = options.take.is_none() && options.where_index.is_none() && !appended_only;
+        if let Some(limit) = options.warn_over_bytes.filter(|_| unlimited) {
+            let mut total_size = 0;
+            for file in &self.files[files.clone()] {
+                total_size += file.size()?;
+            }
+            if total_size > limit {
+                warn(&miette!(
+                    "reading all {} bytes of Avro with no limit, use --take to read fewer records (or --yes to silence this warning)",
+                    total_size
+                ));
+            }
+        }
+
+        let take = options.take;
+        let explode_position = match &options.explode_map {
+            None => None,
+            Some(map_field) => match fields_to_get.iter().position(|field| field == map_field) {
+                Some(position) => Some(position),
+                None => bail!("Map field `{}` to explode is not selected", map_field),
+            },
+        };
+        // Each field is split into its top-level name and the path below it
+        let fields_to_get: Vec<_> = fields_to_get
+            .iter()
+            .map(|field| {
+                let mut path = field_path::parse(field);
+                let name = match path.first() {
+                    Some(&Segment::Field(name)) => {
+                        path.remove(0);
+                        name
+                    }
+                    _ => field.as_str(),
+                };
+                (field, (name, path))
+            })
+            .collect();
+        if !appended_only {
+            self.seen_keys.clear();
+        }
+        let mut extracted_fields = Vec::new();
+        let mut next_index: u64 = 0;
+        let mut done = false;
+        let mut errors = ErrorTolerance::new(options);
+        let coercions: Vec<Option<&FieldCoercion>> = fields_to_get
+            .iter()
+            .map(|(field_name, _)| {
+                options
+                    .coerce
+                    .iter()
+                    .find(|coercion| options.field_matches(field_name, &coercion.field))
+            })
+            .collect();
+        for file in &mut self.files[files] {
+            let size = file.size()?;
+            if appended_only && size <= file.size_read {
+                continue;
+            }
+            let already_read = if appended_only { file.records_read } else { 0 };
+            let mut records_read = already_read;
+            let resume_at = file.position.filter(|_| appended_only);
+
+            let path = file.path().to_owned();
+            let container = file.assumed_schema.is_none();
+            // Kept aside to explain errors with, as the reader borrows the file
+            let in_memory = match &file.source {
+                AvroSource::Memory(bytes) if options.explain_errors && container => {
+                    Some(bytes.clone())
+                }
+                _ => None,
+            };
+            let mut reader = file.reader()?;
+            // From the header the reader read, as the parsed schema drops malformed annotations
+            let declared_logical_types = match &reader {
+                Records::Container(container) if !appended_only => {
+                    schema::declared_logical_types(&container.header()?.schema_json)
+                }
+                _ => Vec::new(),
+            };
+            // Container files carry on from the block reading stopped in. Datums have no
+            // blocks, so those already read are decoded again to get past them
+            let skip = match (&mut reader, resume_at) {
+                (Records::Container(container), Some(position)) => {
+                    container.seek(position).wrap_err_with(|| {
+                        format!("Could not read the records appended to {}", path.display())
+                    })?;
+                    0
+                }
+                _ => already_read,
+            };
+            reader.by_ref().take(skip as usize).for_each(drop);
+            let writer_schema = reader.writer_schema().clone();
+            let scale_decimals = !options.raw_decimals && schema::has_decimals(&writer_schema);
+            let resolved_schema =
+                if options.enum_validate || options.annotate_unions || scale_decimals {
+                    Some(ResolvedSchema::try_from(&writer_schema).into_diagnostic()?)
+                } else {
+                    None
+                };
+            let union_filter = match &options.union_branch {
+                Some(filter) => {
+                    let index = filter.index_in(&writer_schema)?;
+                    match index {
+                        Some(index) => trace!(
+                            "Selecting branch {} of union field `{}` in {}",
+                            index,
+                            filter.field,
+                            path.display()
+                        ),
+                        None => debug!(
+                            "Union field `{}` has no branch `{}` in {}, no records will match",
+                            filter.field,
+                            filter.branch,
+                            path.display()
+                        ),
+                    }
+                    Some((filter, index))
+                }
+                None => None,
+            };
+            // Where each requested field sits in this file's records, found once so that
+            // records can be indexed directly instead of searched by name
+            let schema_fields = record_field_names(&writer_schema);
+            let positions: Vec<Option<usize>> = fields_to_get
+                .iter()
+                .map(|(_, (name, _))| {
+                    schema_fields
+                        .iter()
+                        .position(|field| options.field_matches(field, name))
+                })
+                .collect();
+            let mut logical_types: Vec<(usize, schema::DeclaredLogicalType)> =
+                declared_logical_types
+                    .into_iter()
+                    .filter_map(|declared| {
+                        let position = schema_fields.iter().position(|f| *f == declared.field)?;
+                        Some((position, declared))
+                    })
+                    .collect();
+            let replacements: Vec<Option<Value>> = fields_to_get
+                .iter()
+                .zip(&positions)
+                .map(|((field_name, (_, path)), position)| {
+                    let replacement = options.replace_null.iter().find(|replacement| {
+                        options.field_matches(field_name, &replacement.field)
+                    })?;
+                    let field_schema = match &writer_schema {
+                        Schema::Record(RecordSchema { fields, .. }) if path.is_empty() => {
+                            position.and_then(|position| fields.get(position))
+                        }
+                        _ => None,
+                    };
+                    Some(replacement.typed(field_schema.map(|field| &field.schema)))
+                })
+                .collect();
+            // Only the first value of each field that can't be converted is reported
+            let mut coercion_failed = vec![false; coercions.len()];
+            let schema_id = options.with_schema_id.then(|| schema_id(&writer_schema));
+            let hash_schema = if options.hash {
+                let mut names = schema_fields.clone();
+                names.sort();
+                Some(schema::project(&writer_schema, &names)?)
+            } else {
+                None
+            };
+
+            let mut position = reader.position();
+            for i in already_read as usize.. {
+                position = reader.position();
+                let Some(row) = reader.next() else {
+                    position = reader.position();
+                    break;
+                };
+                if extracted_fields.len() as u32 >= take.unwrap_or(u32::max_value()) {
+                    done = true;
+                    break;
+                }
+
+                let index = next_index;
+                next_index += 1;
+                if let Some(range) = &options.where_index {
+                    if range.is_past(index) {
+                        done = true;
+                        break;
+                    }
+                    if !range.contains(index) {
+                        records_read = i as u64 + 1;
+                        continue;
+                    }
+                }
+
+                let row = match row {
+                    // The end of a file being appended to may not be fully written yet
+                    Err(err) if appended_only && is_truncated(&err) => break,
+                    row => row.into_diagnostic(),
+                };
+                let row = match row {
+                    Err(err) if options.explain_errors && container && !appended_only => {
+                        Err(explain_decode_error(err, &path, in_memory.as_deref(), i))
+                    }
+                    row => row,
+                };
+                let row = match row
+                    .wrap_err_with(|| format!("Could not parse row {} from the Avro", i))
+                {
+                    Ok(row) => row,
+                    Err(err) => {
+                        errors.tolerate(err)?;
+                        records_read = i as u64 + 1;
+                        continue;
+                    }
+                };
+                records_read = i as u64 + 1;
+                if let (true, Some(resolved_schema)) = (options.enum_validate, &resolved_schema) {
+                    if let Err(err) =
+                        schema::check_enums(&row, &writer_schema, resolved_schema.get_names())
+                            .wrap_err_with(|| {
+                                format!("Row {} of {} has an invalid enum", i, path.display())
+                            })
+                    {
+                        if errors.skips() {
+                            errors.tolerate(err)?;
+                            continue;
+                        }
+                        warn(&err);
+                    }
+                }
+                if options.strict_utf8 {
+                    match check_utf8(&row).wrap_err_with(|| {
+                        format!("Row {} of {} has invalid UTF-8", i, path.display())
+                    }) {
+                        Ok(()) => {}
+                        Err(err) => {
+                            errors.tolerate(err)?;
+                            continue;
+                        }
+                    }
+                }
+                if let Value::Record(fields) = row {
+                    // Each field is only reported once, as every record would repeat it
+                    while let Some((found, base_type)) = logical_types.iter().enumerate().find_map(
+                        |(found, (position, declared))| {
+                            let (_, value) = fields.get(*position)?;
+                            Some((found, declared.lost_by(value)?))
+                        },
+                    ) {
+                        let (_, declared) = logical_types.remove(found);
+                        let err = miette!(
+                            "Field `{}` of {} is declared as {} but row {} decoded it as a plain {}, \
+                             the logical type annotation may be malformed",
+                            declared.field,
+                            path.display(),
+                            declared.logical_type,
+                            i,
+                            base_type
+                        );
+                        if options.fail_on_schema_warning {
+                            return Err(err);
+                        }
+                        warn(&err);
+                    }
+                    if let Some((filter, index)) = &union_filter {
+                        if !filter.selects(&fields, *index) {
+                            continue;
+                        }
+                    }
+                    if let Some(range) = &options.time_range {
+                        if !range
+                            .contains(&fields, options)
+                            .wrap_err_with(|| format!("In row {} of {}", i, path.display()))?
+                        {
+                            continue;
+                        }
+                    }
+                    if let Some(filter) = &options.record_filter {
+                        // The filter compares decimals as numbers, so they need their scale
+                        let scaled = match &resolved_schema {
+                            Some(resolved_schema) if scale_decimals => {
+                                match schema::scale_decimals(
+                                    Value::Record(fields.clone()),
+                                    &writer_schema,
+                                    resolved_schema.get_names(),
+                                ) {
+                                    Value::Record(scaled) => Some(scaled),
+                                    _ => None,
+                                }
+                            }
+                            _ => None,
+                        };
+                        match filter
+                            .matches(scaled.as_deref().unwrap_or(&fields))
+                            .wrap_err_with(|| format!("In row {} of {}", i, path.display()))
+                        {
+                            Ok(true) => {}
+                            Ok(false) => continue,
+                            Err(err) => {
+                                errors.tolerate(err)?;
+                                continue;
+                            }
+                        }
+                    }
+                    // Only kept records use up their key, so it is recorded once the record
+                    // can't be dropped any more
+                    let mut dedupe_key = None;
+                    if !options.dedupe_by.is_empty() {
+                        let key: Vec<String> = options
+                            .dedupe_by
+                            .iter()
+                            .map(|key_field| {
+                                fields
+                                    .iter()
+                                    .find(|(n, _)| options.field_matches(n, key_field))
+                                    .map(|(_, value)| AvroValue::from(value.clone()))
+                                    .unwrap_or_else(AvroValue::na)
+                                    .to_string()
+                            })
+                            .collect();
+                        if self.seen_keys.contains(&key) {
+                            continue;
+                        }
+                        dedupe_key = Some(key);
+                    }
+                    let mut extracted_fields_for_row = Vec::new();
+                    for ((field_name, (name, path)), position) in
+                        fields_to_get.iter().zip(&positions)
+                    {
+                        let found = position.and_then(|position| {
+                            fields
+                                .get(position)
+                                .filter(|(n, _)| options.field_matches(n, name))
+                                .or_else(|| {
+                                    fields.iter().find(|(n, _)| options.field_matches(n, name))
+                                })
+                        });
+                        let names = resolved_schema.as_ref().map(|schema| schema.get_names());
+                        let field_schema = match &writer_schema {
+                            Schema::Record(record) => found.and_then(|(n, _)| {
+                                record.lookup.get(n).map(|&i| &record.fields[i].schema)
+                            }),
+                            _ => None,
+                        };
+                        let nested = found.and_then(|(_, field_value)| {
+                            descend(field_value.clone(), field_schema, names, path, options)
+                        });
+                        let field_value_to_insert = match nested {
+                            Some((mut v, v_schema)) => {
+                                if let (Some(v_schema), Some(names)) = (v_schema, names) {
+                                    if scale_decimals {
+                                        v = schema::scale_decimals(v, v_schema, names);
+                                    }
+                                    if options.annotate_unions {
+                                        v = schema::annotate_unions(v, v_schema, names);
+                                    }
+                                }
+                                AvroColumnarValue::from(field_name.to_string(), AvroValue::from(v))
+                            }
+                            None => {
+                                AvroColumnarValue::from(field_name.to_string(), AvroValue::na())
+                            }
+                        };
+                        extracted_fields_for_row.push(field_value_to_insert);
+                    }
+                    if options.drop_empty
+                        && extracted_fields_for_row
+                            .iter()
+                            .all(|value| matches!(value.value(), AvroValue::Na))
+                    {
+                        continue;
+                    }
+                    if let Some(key) = dedupe_key {
+                        self.seen_keys.insert(key);
+                    }
+                    for ((extracted, coercion), failed) in extracted_fields_for_row
+                        .iter_mut()
+                        .zip(&coercions)
+                        .zip(&mut coercion_failed)
+                    {
+                        let (Some(coercion), AvroValue::Value(value)) =
+                            (coercion, &extracted.value)
+                        else {
+                            continue;
+                        };
+                        extracted.value = match coercion.coerce(value) {
+                            Some(coerced) => AvroValue::from(coerced),
+                            None => {
+                                if !*failed {
+                                    *failed = true;
+                                    warn(&miette!(
+                                        "Can't convert `{}` in row {} of {} to a {:?}, showing N/A instead",
+                                        AvroValue::from(value.clone()),
+                                        i,
+                                        path.display(),
+                                        coercion.to
+                                    ));
+                                }
+                                AvroValue::na()
+                            }
+                        };
+                    }
+                    for (extracted, replacement) in
+                        extracted_fields_for_row.iter_mut().zip(&replacements)
+                    {
+                        if let Some(replacement) = replacement {
+                            if is_null_or_na(&extracted.value) {
+                                extracted.value = AvroValue::from(replacement.clone());
+                            }
+                        }
+                    }
+                    if let Some(hash_schema) = &hash_schema {
+                        let hash = record_hash(&fields, hash_schema).wrap_err_with(|| {
+                            format!("Could not hash row {} of {}", i, path.display())
+                        })?;
+                        extracted_fields_for_row.push(AvroColumnarValue::from(
+                            HASH_COLUMN.to_owned(),
+                            AvroValue::from(Value::String(hash)),
+                        ));
+                    }
+                    if options.with_size {
+                        // Records are stored without framing, so this is their size in the
+                        // file, before compression
+                        let size = to_avro_datum(&writer_schema, Value::Record(fields.clone()))
+                            .into_diagnostic()
+                            .wrap_err_with(|| {
+                                format!("Could not measure row {} of {}", i, path.display())
+                            })?
+                            .len();
+                        extracted_fields_for_row.push(AvroColumnarValue::from(
+                            SIZE_COLUMN.to_owned(),
+                            AvroValue::from(Value::Long(size as i64)),
+                        ));
+                    }
+                    if let Some(schema_id) = &schema_id {
+                        extracted_fields_for_row.insert(
+                            0,
+                            AvroColumnarValue::from(
+                                SCHEMA_COLUMN.to_owned(),
+                                AvroValue::from(Value::String(schema_id.clone())),
+                            ),
+                        );
+                    }
+                    match explode_position {
+                        Some(position) => {
+                            let (rows, truncated) = explode(
+                                extracted_fields_for_row,
+                                position + usize::from(schema_id.is_some()),
+                                options.explode_depth.max(1),
+                                options.max_exploded_rows,
+                            );
+                            if truncated {
+                                warn(&miette!(
+                                    "row {} of {} explodes into more than {} rows, keeping the first ones",
+                                    i,
+                                    path.display(),
+                                    rows.len()
+                                ));
+                            }
+                            extracted_fields.extend(rows)
+                        }
+                        None => extracted_fields.push(extracted_fields_for_row),
+                    }
+                }
+            }
+
+            drop(reader);
+            debug!(
+                "Read {} records from {}, {} rows extracted so far",
+                records_read - already_read,
+                path.display(),
+                extracted_fields.len()
+            );
+            file.records_read = records_read;
+            file.position = position;
+            file.size_read = size;
+            if done {
+                break;
+            }
+        }
+
+        Ok(extracted_fields)
+    }
+}
+
+/// Add where a record failed to decode to its error, going back over the file block by block.
+/// Files held in memory, like those read from stdin or extracted from archives, are read from
+/// `in_memory` instead of `path`
+fn explain_decode_error(
+    err: Report,
+    path: &Path,
+    in_memory: Option<&[u8]>,
+    index: usize,
+) -> Report {
+    let explanation = match in_memory {
+        Some(bytes) => crate::container::explain_decode_error(bytes, index as u64),
+        None => fs::read(path)
+            .into_diagnostic()
+            .and_then(|bytes| crate::container::explain_decode_error(&bytes, index as u64)),
+    };
+    match explanation {
+        Ok(Some(explanation)) => err.wrap_err(format!("{} in {}", explanation, path.display())),
+        Ok(None) => err,
+        Err(explain_err) => err.wrap_err(format!("Could not explain the error: {}", explain_err)),
+    }
+}
+
+/// Whether a record couldn't be read because the input ended partway through it
+fn is_truncated(err: &apache_avro::Error) -> bool {
+    std::iter::successors(Some(err as &(dyn std::error::Error + 'static)), |err| {
+        err.source()
+    })
+    .any(|err| {
+        err.downcast_ref::<std::io::Error>()
+            .is_some_and(|err| err.kind() == std::io::ErrorKind::UnexpectedEof)
+    })
+}
+
+/// Hash a record with SHA-256 over its Avro encoding with the fields sorted by name,
+/// so it hashes the same whatever order its schema declares the fields in
+fn record_hash(fields: &[(String, Value)], sorted_schema: &Schema) -> Result<String> {
+    let mut sorted = fields.to_vec();
+    sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let encoded = to_avro_datum(sorted_schema, Value::Record(sorted)).into_diagnostic()?;
+    Ok(Sha256::digest(encoded)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+fn is_null_or_na(value: &AvroValue) -> bool {
+    match value {
+        AvroValue::Na | AvroValue::Value(Value::Null) => true,
+        AvroValue::Value(Value::Union(_, value)) => matches!(**value, Value::Null),
+        AvroValue::Value(_) => false,
+    }
+}
+
+/// Identify a writer schema by its full name, or by its fingerprint when it isn't named
+fn schema_id(schema: &Schema) -> String {
+    match schema.name() {
+        Some(name) => name.fullname(None),
+        None => schema.fingerprint::<Sha256>().to_string(),
+    }
+}
+
+fn record_field_names(schema: &Schema) -> Vec<String> {
+    match schema {
+        Schema::Record(RecordSchema { fields, .. }) => {
+            fields.iter().map(|field| field.name.clone()).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Follow a path into nested records, maps and arrays, looking through unions, with
+/// negative indices counting from the end of arrays. Also gives the schema of the value
+/// found, when the schema of `value` and the names it refers to are known. A missing field
+/// or key, an out of bounds index, or a value of the wrong type gives None.
+fn descend<'s>(
+    mut value: Value,
+    mut schema: Option<&'s Schema>,
+    names: Option<&NamesRef<'s>>,
+    path: &[Segment],
+    options: &GetOptions,
+) -> Option<(Value, Option<&'s Schema>)> {
+    let resolve = |schema: Option<&'s Schema>| Some(schema::resolve(schema?, names?));
+    for segment in path {
+        while let Value::Union(index, inner) = value {
+            schema = match resolve(schema) {
+                Some(Schema::Union(union)) => union.variants().get(index as usize),
+                _ => None,
+            };
+            value = *inner;
+        }
+        (value, schema) = match (segment, value, resolve(schema)) {
+            (Segment::Field(segment), Value::Record(fields), record_schema) => {
+                let (name, value) = fields
+                    .into_iter()
+                    .find(|(name, _)| options.field_matches(name, segment))?;
+                let schema = match record_schema {
+                    Some(Schema::Record(record)) => {
+                        record.lookup.get(&name).map(|&i| &record.fields[i].schema)
+                    }
+                    _ => None,
+                };
+                (value, schema)
+            }
+            (Segment::Field(segment), Value::Map(mut entries), map_schema) => {
+                let value = entries.remove(*segment)?;
+                let schema = match map_schema {
+                    Some(Schema::Map(MapSchema { types, .. })) => Some(&**types),
+                    _ => None,
+                };
+                (value, schema)
+            }
+            (&Segment::Index(index), Value::Array(mut items), array_schema) => {
+                let len = items.len() as i64;
+                let index = if index < 0 { len + index } else { index };
+                if !(0..len).contains(&index) {
+                    return None;
+                }
+                let schema = match array_schema {
+                    Some(Schema::Array(ArraySchema { items, .. })) => Some(&**items),
+                    _ => None,
+                };
+                (items.swap_remove(index as usize), schema)
+            }
+            _ => return None,
+        };
+    }
+    Some((value, schema))
+}
+
+/// Check that no string in a value holds the U+FFFD replacement character, which
+/// lenient decoding puts in place of invalid UTF-8
+fn check_utf8(value: &Value) -> Result<()> {
+    match value {
+        Value::String(s) if s.contains(char::REPLACEMENT_CHARACTER) => {
+            bail!("String `{}` contains a replacement character (U+FFFD)", s)
+        }
+        Value::Record(fields) => fields.iter().try_for_each(|(name, value)| {
+            check_utf8(value).wrap_err_with(|| format!("In field `{}`", name))
+        }),
+        Value::Map(entries) => entries.iter().try_for_each(|(key, value)| {
+            if key.contains(char::REPLACEMENT_CHARACTER) {
+                bail!(
+                    "Map key `{}` contains a replacement character (U+FFFD)",
+                    key
+                );
+            }
+            check_utf8(value).wrap_err_with(|| format!("In map entry `{}`", key))
+        }),
+        Value::Array(items) => items.iter().try_for_each(check_utf8),
+        Value::Union(_, inner) => check_utf8(inner),
+        _ => Ok(()),
+    }
+}
+
+/// Name of the key column added by exploding the given level: `_key`, then `_key2`...
+fn exploded_key(level: usize) -> String {
+    match level {
+        1 => EXPLODED_KEY.to_owned(),
+        _ => format!("{}{}", EXPLODED_KEY, level),
+    }
+}
+
+/// Explode the map or array at `position` in a row, then the values of its entries, down to
+/// `depth` levels. Each level adds a key column before `_value`, so rows are the cartesian
+/// expansion of the nested entries. Returns the rows, and whether some were left out to
+/// stay within `max_rows`.
+fn explode(
+    row: Vec<AvroColumnarValue>,
+    position: usize,
+    depth: usize,
+    max_rows: Option<usize>,
+) -> (AvroData, bool) {
+    let max_rows = max_rows.unwrap_or(usize::MAX);
+    let mut rows = vec![row];
+    let mut truncated = false;
+    for level in 1..=depth {
+        // The value to explode comes after the keys of the levels above
+        let value_position = position + level - 1;
+        let mut exploded = Vec::new();
+        for row in rows {
+            exploded.extend(explode_entries(row, value_position, exploded_key(level)));
+            if exploded.len() > max_rows {
+                exploded.truncate(max_rows);
+                truncated = true;
+                break;
+            }
+        }
+        rows = exploded;
+    }
+    (rows, truncated)
+}
+
+/// Replace the map or array at `position` in a row by a `key` column and a `_value` column,
+/// making one row per entry, in key order for maps. An array's keys are the indices of its
+/// items. A map or array without entries gives no rows, and a value that is neither is kept
+/// in the `_value` column with an N/A key.
+fn explode_entries(
+    mut row: Vec<AvroColumnarValue>,
+    position: usize,
+    key: String,
+) -> Vec<Vec<AvroColumnarValue>> {
+    let mut value = row.remove(position).value;
+    while let AvroValue::Value(Value::Union(_, inner)) = value {
+        value = AvroValue::from(*inner);
+    }
+    let entry_row = |entry_key: AvroValue, value: AvroValue| {
+        let mut exploded = row.clone();
+        exploded.splice(
+            position..position,
+            [
+                AvroColumnarValue::from(key.clone(), entry_key),
+                AvroColumnarValue::from(EXPLODED_VALUE.to_owned(), value),
+            ],
+        );
+        exploded
+    };
+
+    let map = match value {
+        AvroValue::Value(Value::Map(map)) => map,
+        AvroValue::Value(Value::Array(items)) => {
+            return items
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    entry_row(
+                        AvroValue::from(Value::Long(index as i64)),
+                        AvroValue::from(item),
+                    )
+                })
+                .collect()
+        }
+        value => return vec![entry_row(AvroValue::na(), value)],
+    };
+    let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    entries
+        .into_iter()
+        .map(|(key, value)| entry_row(AvroValue::from(Value::String(key)), AvroValue::from(value)))
+        .collect()
+}
+
+/// Leave out the fields named in `exclude`, comparing names regardless of case with
+/// `ignore_case`. Excluding a field wins over selecting it, and names that match no field
+/// are ignored.
+pub fn exclude_fields(fields: Vec<String>, exclude: &[String], ignore_case: bool) -> Vec<String> {
+    fields
+        .into_iter()
+        .filter(|field| {
+            !exclude.iter().any(|excluded| {
+                if ignore_case {
+                    field.eq_ignore_ascii_case(excluded)
+                } else {
+                    field == excluded
+                }
+            })
+        })
+        .collect()
+}
+
+/// Expand the records and maps in extracted rows into one dotted column per leaf value,
+/// e.g. `address.city`. Returns the columns of all rows, in the order they were first
+/// seen, with each row holding N/A for the columns it doesn't have.
+///
+/// With `max_array_cols`, arrays of records that have at most that many items are
+/// expanded too, by position, e.g. `items.0.name`. Longer arrays stay whole.
+pub fn flatten_all(data: AvroData, max_array_cols: Option<usize>) -> (Vec<String>, AvroData) {
+    let mut columns: Vec<String> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let flattened: Vec<Vec<(usize, AvroValue)>> = data
+        .into_iter()
+        .map(|row| {
+            let mut leaves = Vec::new();
+            for column in row {
+                flatten_value(column.name, column.value, max_array_cols, &mut leaves);
+            }
+            leaves
+                .into_iter()
+                .map(|(name, value)| {
+                    let position = *positions.entry(name.clone()).or_insert_with(|| {
+                        columns.push(name);
+                        columns.len() - 1
+                    });
+                    (position, value)
+                })
+                .collect()
+        })
+        .collect();
+
+    let data = flattened
+        .into_iter()
+        .map(|leaves| {
+            let mut row: Vec<AvroColumnarValue> = columns
+                .iter()
+                .map(|name| AvroColumnarValue::from(name.clone(), AvroValue::na()))
+                .collect();
+            for (position, value) in leaves {
+                row[position].value = value;
+            }
+            row
+        })
+        .collect();
+    (columns, data)
+}
+
+fn flatten_value(
+    name: String,
+    mut value: AvroValue,
+    max_array_cols: Option<usize>,
+    leaves: &mut Vec<(String, AvroValue)>,
+) {
+    while let AvroValue::Value(Value::Union(_, inner)) = value {
+        value = AvroValue::from(*inner);
+    }
+    match value {
+        AvroValue::Value(Value::Record(fields)) => {
+            for (field, value) in fields {
+                flatten_value(
+                    format!("{}.{}", name, field),
+                    AvroValue::from(value),
+                    max_array_cols,
+                    leaves,
+                );
+            }
+        }
+        AvroValue::Value(Value::Map(map)) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (key, value) in entries {
+                flatten_value(
+                    format!("{}.{}", name, key),
+                    AvroValue::from(value),
+                    max_array_cols,
+                    leaves,
+                );
+            }
+        }
+        AvroValue::Value(Value::Array(items))
+            if !items.is_empty()
+                && max_array_cols.is_some_and(|max| items.len() <= max)
+                && items.iter().all(is_record) =>
+        {
+            for (position, item) in items.into_iter().enumerate() {
+                flatten_value(
+                    format!("{}.{}", name, position),
+                    AvroValue::from(item),
+                    max_array_cols,
+                    leaves,
+                );
+            }
+        }
+        value => leaves.push((name, value)),
+    }
+}
+
+fn is_record(value: &Value) -> bool {
+    match value {
+        Value::Record(_) => true,
+        Value::Union(_, value) => is_record(value),
+        _ => false,
+    }
+}
+
+/// The leading components of a glob that have no wildcards
+fn glob_literal_prefix(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|component| {
+            !component
+                .as_os_str()
+                .to_string_lossy()
+                .contains(['*', '?', '['])
+        })
+        .collect()
+}
+
+/// A path matched by a glob
+#[derive(Debug, Clone)]
+pub struct GlobMatch {
+    pub path: PathBuf,
+    pub kind: PathKind,
+    /// Why the path is left out, if it is
+    pub skipped: Option<String>,
+}
+
+/// What a matched path is, without following it if it is a symlink
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathKind {
+    File,
+    /// A symlink, and the path it points to
+    Symlink(PathBuf),
+    Directory,
+    /// Anything else, like a named pipe or a device
+    Other,
+}
+
+impl fmt::Display for PathKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathKind::File => write!(f, "file"),
+            PathKind::Symlink(target) => write!(f, "symlink to {}", target.display()),
+            PathKind::Directory => write!(f, "directory"),
+            PathKind::Other => write!(f, "not a regular file"),
+        }
+    }
+}
+
+/// Match a glob against the filesystem, as `CliService::from_glob` does, listing every match
+/// including those it leaves out
+///
+/// # Arguments
+///
+/// * `pattern` - A glob to match against Avro files
+/// * `follow_symlinks` - When false, matches that are symlinks or that go through a
+///   symlinked directory below the literal start of the glob are left out
+pub fn expand_glob(pattern: &str, follow_symlinks: bool) -> Result<Vec<GlobMatch>> {
+    let literal_prefix = glob_literal_prefix(pattern);
+    let mut seen: HashMap<PathBuf, PathBuf> = HashMap::new();
+    let mut matches = Vec::new();
+    for entry in glob_with(pattern, MatchOptions::new())
+        .into_diagnostic()
+        .wrap_err("Failed to read glob pattern")?
+    {
+        let path = match entry {
+            Ok(path) => path,
+            Err(e) => bail!("{:?}", e),
+        };
+        let metadata = fs::symlink_metadata(&path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Could not read metadata of {}", path.display()))?;
+        let kind = if metadata.is_symlink() {
+            PathKind::Symlink(fs::read_link(&path).into_diagnostic()?)
+        } else if metadata.is_file() {
+            PathKind::File
+        } else if metadata.is_dir() {
+            PathKind::Directory
+        } else {
+            PathKind::Other
+        };
+
+        let skipped = if !follow_symlinks && through_symlink(&path, &literal_prefix) {
+            Some("it is or goes through a symlink".to_owned())
+        } else {
+            let canonical = fs::canonicalize(&path)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Could not resolve path {}", path.display()))?;
+            match seen.get(&canonical) {
+                Some(first) => Some(format!("it is the same file as {}", first.display())),
+                None => {
+                    seen.insert(canonical, path.clone());
+                    None
+                }
+            }
+        };
+        if let Some(reason) = &skipped {
+            debug!("Skipping {}, {}", path.display(), reason);
+        }
+        matches.push(GlobMatch {
+            path,
+            kind,
+            skipped,
+        });
+    }
+    Ok(matches)
+}
+
+/// Whether a path is a symlink or has a symlinked directory below `prefix`
+fn through_symlink(path: &Path, prefix: &Path) -> bool {
+    path.ancestors()
+        .take_while(|ancestor| *ancestor != prefix && !ancestor.as_os_str().is_empty())
+        .any(|ancestor| {
+            fs::symlink_metadata(ancestor).is_ok_and(|metadata| metadata.file_type().is_symlink())
+        })
+}
+
+fn sort_files_by_key<K: Ord>(
+    files: &mut Vec<AvroFile>,
+    key: impl Fn(&AvroFile) -> Result<K>,
+) -> Result<()> {
+    let mut keyed = files
+        .drain(..)
+        .map(|file| Ok((key(&file)?, file)))
+        .collect::<Result<Vec<_>>>()?;
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    files.extend(keyed.into_iter().map(|(_, file)| file));
+    Ok(())
+}
+
+/// Adds up the bytes read through it
+struct CountingReader<'a, R> {
+    inner: R,
+    count: &'a Cell<u64>,
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.set(self.count.get() + read as u64);
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for CountingReader<'_, R> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// Decides whether a record that can't be decoded or fails validation is skipped or stops
+/// processing, counting those skipped
+struct ErrorTolerance {
+    skip: bool,
+    limit: Option<u64>,
+    count: u64,
+}
+
+impl ErrorTolerance {
+    fn new(options: &GetOptions) -> Self {
+        ErrorTolerance {
+            skip: options.skip_errors || options.abort_after_errors.is_some(),
+            limit: options.abort_after_errors,
+            count: 0,
+        }
+    }
+
+    /// Whether errors are skipped rather than fatal
+    fn skips(&self) -> bool {
+        self.skip
+    }
+
+    /// Warn about an error and carry on, or return it when errors aren't skipped or too
+    /// many have been
+    fn tolerate(&mut self, err: Report) -> Result<()> {
+        if !self.skip {
+            return Err(err);
+        }
+        self.count += 1;
+        match self.limit {
+            Some(limit) if self.count > limit => Err(err.wrap_err(format!(
+                "Aborting after {} errors, more than the {} allowed",
+                self.count, limit
+            ))),
+            _ => {
+                warn(&err);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn read_whole(mut file: &File, path: &Path) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    file.seek(std::io::SeekFrom::Start(0))
+        .and_then(|_| file.read_to_end(&mut bytes))
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Could not read {}", path.display()))?;
+    Ok(bytes)
+}
+
+/// Print a non-fatal error and its causes to stderr
+pub(crate) fn warn(err: &Report) {
+    let causes: Vec<String> = err.chain().map(|cause| cause.to_string()).collect();
+    eprintln!("Warning: {}", causes.join(": "));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apache_avro::schema::UnionSchema;
+    use std::path::Path;
+
+    #[test]
+    fn test_get_all_field_names() {
+        println!("asdas");
+        let path_to_test_avro = Path::new("./test_assets/bttf.avro")
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let mut cli = CliService::from(path_to_test_avro).unwrap();
+        let field_names = cli.get_all_field_names().unwrap();
+        assert_eq!(field_names, vec!["firstName", "lastName", "age"]);
+    }
+
+    #[test]
+    fn test_parse_index_range() {
+        let range: IndexRange = "100..105".parse().unwrap();
+        assert!(!range.contains(99));
+        assert!(range.contains(100));
+        assert!(range.contains(104));
+        assert!(!range.contains(105));
+        assert!(range.is_past(105));
+
+        let range: IndexRange = "100..=105".parse().unwrap();
+        assert!(range.contains(105));
+        assert!(!range.contains(106));
+
+        let range: IndexRange = "3".parse().unwrap();
+        assert!(range.contains(3));
+        assert!(!range.contains(4));
+
+        let range: IndexRange = "10..".parse().unwrap();
+        assert!(range.contains(1_000_000));
+        assert!(!range.is_past(u64::MAX));
+
+        let max = u64::MAX.to_string();
+        let range: IndexRange = format!("10..={}", max).parse().unwrap();
+        assert!(range.contains(u64::MAX));
+        let range: IndexRange = max.parse().unwrap();
+        assert!(range.contains(u64::MAX));
+        assert!(!range.contains(u64::MAX - 1));
+
+        assert!("a..b".parse::<IndexRange>().is_err());
+    }
+
+    #[test]
+    fn test_glob_literal_prefix() {
+        assert_eq!(
+            glob_literal_prefix("data/2024/*/part-?.avro"),
+            Path::new("data/2024")
+        );
+        assert_eq!(glob_literal_prefix("*.avro"), Path::new(""));
+        assert_eq!(
+            glob_literal_prefix("/srv/data/file.avro"),
+            Path::new("/srv/data/file.avro")
+        );
+    }
+
+    #[test]
+    fn test_exclude_fields() {
+        let fields = || vec!["id".to_owned(), "Name".to_owned(), "age".to_owned()];
+        let exclude = ["name".to_owned(), "age".to_owned(), "missing".to_owned()];
+
+        assert_eq!(exclude_fields(fields(), &exclude, false), ["id", "Name"]);
+        assert_eq!(exclude_fields(fields(), &exclude, true), ["id"]);
+        assert_eq!(exclude_fields(fields(), &[], false), fields());
+    }
+
+    #[test]
+    fn test_descend() {
+        let geo = Value::Record(vec![("lat".to_owned(), Value::Double(51.5))]);
+        let address = Value::Record(vec![
+            ("city".to_owned(), Value::String("London".to_owned())),
+            ("geo".to_owned(), Value::Union(1, Box::new(geo))),
+            (
+                "tags".to_owned(),
+                Value::Map(HashMap::from([("zone".to_owned(), Value::Int(1))])),
+            ),
+            (
+                "lines".to_owned(),
+                Value::Array(vec![
+                    Value::Record(vec![("n".to_owned(), Value::Int(1))]),
+                    Value::Record(vec![("n".to_owned(), Value::Int(2))]),
+                ]),
+            ),
+        ]);
+        let options = GetOptions::default();
+        let get = |path: &str| {
+            let path = field_path::parse(path);
+            descend(address.clone(), None, None, &path, &options).map(|(value, _)| value)
+        };
+
+        assert_eq!(get("city"), Some(Value::String("London".to_owned())));
+        assert_eq!(get("tags.zone"), Some(Value::Int(1)));
+        assert_eq!(get("geo.lat"), Some(Value::Double(51.5)));
+        assert_eq!(get("zip"), None);
+        assert_eq!(get("country.name"), None);
+        assert_eq!(get("city.name"), None);
+        assert_eq!(get("lines[1].n"), Some(Value::Int(2)));
+        assert_eq!(get("lines[-2].n"), Some(Value::Int(1)));
+        assert_eq!(get("lines[2].n"), None);
+        assert_eq!(get("city[0]"), None);
+    }
+
+    #[test]
+    fn test_time_range() {
+        let since = TimeRange::parse_bound("2023-01-01").unwrap();
+        assert_eq!(since, "2023-01-01T00:00:00Z".parse::<Timestamp>().unwrap());
+        assert_eq!(
+            TimeRange::parse_bound("2023-01-01T02:00:00+02:00").unwrap(),
+            since
+        );
+        assert!(TimeRange::parse_bound("yesterday").is_err());
+
+        let range = TimeRange {
+            field: "ts".to_owned(),
+            since: Some(since),
+            until: Some(TimeRange::parse_bound("2023-02-01").unwrap()),
+        };
+        let options = GetOptions::default();
+        let record = |value: Value| vec![("ts".to_owned(), value)];
+        let millis =
+            |s: &str| Value::TimestampMillis(s.parse::<Timestamp>().unwrap().as_millisecond());
+        assert!(range
+            .contains(&record(millis("2023-01-01T00:00:00Z")), &options)
+            .unwrap());
+        assert!(!range
+            .contains(&record(millis("2023-02-01T00:00:00Z")), &options)
+            .unwrap());
+        assert!(range
+            .contains(&record(Value::Date(19_380)), &options)
+            .unwrap());
+        assert!(!range
+            .contains(&record(Value::Union(0, Box::new(Value::Null))), &options)
+            .unwrap());
+        assert!(!range.contains(&[], &options).unwrap());
+        assert!(range.contains(&record(Value::Int(0)), &options).is_err());
+    }
+
+    #[test]
+    fn test_null_replacement() {
+        let count: NullReplacement = "count=0".parse().unwrap();
+        let nullable_long =
+            Schema::Union(UnionSchema::new(vec![Schema::Null, Schema::Long]).unwrap());
+        assert_eq!(count.typed(Some(&nullable_long)), Value::Long(0));
+        assert_eq!(
+            count.typed(Some(&Schema::String)),
+            Value::String("0".to_owned())
+        );
+        assert_eq!(count.typed(None), Value::String("0".to_owned()));
+
+        let status: NullReplacement = "status=unknown".parse().unwrap();
+        assert_eq!(
+            status.typed(Some(&nullable_long)),
+            Value::String("unknown".to_owned())
+        );
+        assert!("=0".parse::<NullReplacement>().is_err());
+    }
+
+    #[test]
+    fn test_field_coercion() {
+        let id: FieldCoercion = "id=long".parse().unwrap();
+        let union = |index, value| Value::Union(index, Box::new(value));
+        assert_eq!(id.coerce(&union(1, Value::Long(7))), Some(Value::Long(7)));
+        assert_eq!(
+            id.coerce(&union(2, Value::String(" 7".to_owned()))),
+            Some(Value::Long(7))
+        );
+        assert_eq!(id.coerce(&Value::Double(7.5)), None);
+        assert_eq!(id.coerce(&union(0, Value::Null)), Some(Value::Null));
+
+        let price: FieldCoercion = "price=double".parse().unwrap();
+        let decimal = apache_avro::BigDecimal::new(num_bigint::BigInt::from(1250), 2);
+        assert_eq!(
+            price.coerce(&union(1, Value::BigDecimal(decimal))),
+            Some(Value::Double(12.5))
+        );
+
+        let id: FieldCoercion = "id=string".parse().unwrap();
+        assert_eq!(
+            id.coerce(&Value::Long(7)),
+            Some(Value::String("7".to_owned()))
+        );
+        let flag: FieldCoercion = "flag=boolean".parse().unwrap();
+        assert_eq!(flag.coerce(&Value::Int(1)), Some(Value::Boolean(true)));
+        assert_eq!(flag.coerce(&Value::String("maybe".to_owned())), None);
+
+        assert!("id=uuid".parse::<FieldCoercion>().is_err());
+        assert!("long".parse::<FieldCoercion>().is_err());
+    }
+
+    #[test]
+    fn test_flatten_all() {
+        let address =
+            |city: &str| Value::Record(vec![("city".to_owned(), Value::String(city.to_owned()))]);
+        let data = vec![
+            vec![
+                AvroColumnarValue::from(
+                    "name".to_owned(),
+                    AvroValue::from(Value::String("Marty".to_owned())),
+                ),
+                AvroColumnarValue::from(
+                    "address".to_owned(),
+                    AvroValue::from(Value::Union(1, Box::new(address("Hill Valley")))),
+                ),
+            ],
+            vec![
+                AvroColumnarValue::from(
+                    "name".to_owned(),
+                    AvroValue::from(Value::String("Doc".to_owned())),
+                ),
+                AvroColumnarValue::from(
+                    "tags".to_owned(),
+                    AvroValue::from(Value::Map(
+                        [("inventor".to_owned(), Value::Boolean(true))]
+                            .into_iter()
+                            .collect(),
+                    )),
+                ),
+            ],
+        ];
+
+        let (columns, data) = flatten_all(data, None);
+        assert_eq!(columns, vec!["name", "address.city", "tags.inventor"]);
+        let rendered: Vec<Vec<String>> = data
+            .iter()
+            .map(|row| row.iter().map(|value| value.value().to_string()).collect())
+            .collect();
+        assert_eq!(
+            rendered,
+            vec![
+                vec!["Marty", "Hill Valley", "N/A"],
+                vec!["Doc", "N/A", "true"],
+            ]
+        );
+
+        let homes = |cities: &[&str]| {
+            vec![AvroColumnarValue::from(
+                "homes".to_owned(),
+                AvroValue::from(Value::Array(
+                    cities.iter().map(|city| address(city)).collect(),
+                )),
+            )]
+        };
+        let data = vec![homes(&["Hill Valley", "Paris"]), homes(&["A", "B", "C"])];
+        let (columns, data) = flatten_all(data, Some(2));
+        assert_eq!(columns, vec!["homes.0.city", "homes.1.city", "homes"]);
+        assert_eq!(data[0][1].value().to_string(), "Paris");
+        assert!(matches!(data[0][2].value(), AvroValue::Na));
+        assert!(matches!(data[1][0].value(), AvroValue::Na));
+    }
+
+    #[test]
+    fn test_explode() {
+        let long = |n: i64| Value::Long(n);
+        let row = vec![
+            AvroColumnarValue::from(
+                "grid".to_owned(),
+                AvroValue::from(Value::Array(vec![
+                    Value::Array(vec![long(1), long(2)]),
+                    Value::Array(vec![]),
+                    Value::Array(vec![long(3)]),
+                ])),
+            ),
+            AvroColumnarValue::from("id".to_owned(), AvroValue::from(long(7))),
+        ];
+        let render = |rows: AvroData| -> Vec<Vec<String>> {
+            rows.iter()
+                .map(|row| row.iter().map(|value| value.value().to_string()).collect())
+                .collect()
+        };
+
+        let (rows, truncated) = explode(row.clone(), 0, 2, None);
+        assert!(!truncated);
+        assert_eq!(
+            render(rows),
+            vec![
+                vec!["0", "0", "1", "7"],
+                vec!["0", "1", "2", "7"],
+                vec!["2", "0", "3", "7"],
+            ]
+        );
+
+        let (rows, truncated) = explode(row.clone(), 0, 1, None);
+        assert!(!truncated);
+        assert_eq!(rows.len(), 3);
+
+        let (rows, truncated) = explode(row, 0, 2, Some(2));
+        assert!(truncated);
+        assert_eq!(rows.len(), 2);
+
+        let options = GetOptions {
+            explode_map: Some("grid".to_owned()),
+            explode_depth: 2,
+            ..GetOptions::default()
+        };
+        assert_eq!(
+            options.columns(&["grid".to_owned(), "id".to_owned()]),
+            vec!["_key", "_key2", "_value", "id"]
+        );
+    }
+
+    #[test]
+    fn test_columns() {
+        let options = GetOptions {
+            hash: true,
+            with_size: true,
+            with_schema_id: true,
+            ..GetOptions::default()
+        };
+        assert_eq!(
+            options.columns(&["id".to_owned()]),
+            vec!["_schema", "id", "_hash", "_size_bytes"]
+        );
+    }
+
+    #[test]
+    fn test_get_fields_across_schemas() {
+        use apache_avro::Writer;
+
+        let temp = crate::testing::TempDir::new("get-fields-across-schemas");
+        let dir = temp.path();
+        let write = |name: &str, schema: &str, record: Vec<(&str, Value)>| {
+            let schema = Schema::parse_str(schema).unwrap();
+            let mut writer = Writer::new(&schema, File::create(dir.join(name)).unwrap());
+            writer
+                .append(Value::Record(
+                    record
+                        .into_iter()
+                        .map(|(name, value)| (name.to_owned(), value))
+                        .collect(),
+                ))
+                .unwrap();
+            writer.flush().unwrap();
+        };
+        write(
+            "a.avro",
+            r#"{"type": "record", "name": "person", "fields": [
+                {"name": "firstName", "type": "string"},
+                {"name": "age", "type": "int"}
+            ]}"#,
+            vec![
+                ("firstName", Value::String("Marty".to_owned())),
+                ("age", Value::Int(17)),
+            ],
+        );
+        write(
+            "b.avro",
+            r#"{"type": "record", "name": "person", "fields": [
+                {"name": "age", "type": "int"},
+                {"name": "lastName", "type": "string"},
+                {"name": "firstName", "type": "string"}
+            ]}"#,
+            vec![
+                ("age", Value::Int(65)),
+                ("lastName", Value::String("Brown".to_owned())),
+                ("firstName", Value::String("Emmett".to_owned())),
+            ],
+        );
+
+        let mut cli = CliService::from(dir.join("*.avro").to_string_lossy().into_owned()).unwrap();
+        cli.sort_files(FileOrder::Name).unwrap();
+        let fields = vec![
+            "firstName".to_owned(),
+            "lastName".to_owned(),
+            "age".to_owned(),
+        ];
+        let rows: Vec<Vec<String>> = cli
+            .get_fields(&fields, &GetOptions::default())
+            .unwrap()
+            .iter()
+            .map(|row| row.iter().map(|value| value.value().to_string()).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![vec!["Marty", "N/A", "17"], vec!["Emmett", "Brown", "65"]]
+        );
+
+        let options = GetOptions {
+            fields_ci: true,
+            ..GetOptions::default()
+        };
+        assert_eq!(
+            cli.resolve_field_names_ci(vec!["LASTNAME".to_owned(), "AGE".to_owned()])
+                .unwrap(),
+            vec!["lastName", "age"]
+        );
+        let rows = cli.get_fields(&["LASTNAME".to_owned()], &options).unwrap();
+        assert_eq!(rows[1][0].value().to_string(), "Brown");
+    }
+
+    #[test]
+    fn test_get_fields() {
+        println!("asdas");
+        let path_to_test_avro = Path::new("./test_assets/bttf.avro")
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let _cli = CliService::from(path_to_test_avro).unwrap();
+        // let field_names = cli.get_fields(vec!["firstName", "age"], None);
+        // assert_eq!(field_names, vec!["firstName", "lastName", "age"]);
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let bytes = fs::read("./test_assets/bttf.avro").unwrap();
+        let mut cli = CliService::from_reader(Cursor::new(bytes)).unwrap();
+        assert_eq!(cli.files()[0].path(), Path::new("-"));
+        assert_eq!(
+            cli.get_all_field_names().unwrap(),
+            vec!["firstName", "lastName", "age"]
+        );
+        // Each read starts over from the beginning, although the input can't be read again
+        let fields = vec!["firstName".to_owned()];
+        assert_eq!(
+            cli.get_fields(&fields, &GetOptions::default())
+                .unwrap()
+                .len(),
+            4
+        );
+        assert_eq!(
+            cli.get_fields(&fields, &GetOptions::default())
+                .unwrap()
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_count_records() {
+        let mut cli = CliService::from("./test_assets/bttf*.avro".to_owned()).unwrap();
+        let counts: Vec<u64> = cli
+            .count_records()
+            .unwrap()
+            .into_iter()
+            .map(|(_, count)| count)
+            .collect();
+        assert_eq!(counts, vec![4, 4]);
+    }
+
+    #[test]
+    fn test_schema_clusters() {
+        let mut cli = CliService::from("./test_assets/bttf*.avro".to_owned()).unwrap();
+        let clusters = cli.schema_clusters().unwrap();
+        assert_eq!(clusters.len(), 1);
+        let names: Vec<_> = clusters[0]
+            .1
+            .iter()
+            .map(|path| path.file_name().unwrap())
+            .collect();
+        assert_eq!(names, vec!["bttf.avro", "bttfCopy.avro"]);
+    }
+
+    /// A container file with a block for each string, where the blocks at `corrupt` can't
+    /// be decoded
+    fn file_of_blocks(strings: &[&str], corrupt: &[usize]) -> Vec<u8> {
+        use apache_avro::Writer;
+
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "r", "fields": [{"name": "n", "type": "string"}]}"#,
+        )
+        .unwrap();
+        let mut writer = Writer::new(&schema, Vec::new());
+        for string in strings {
+            writer
+                .append(Value::Record(vec![(
+                    "n".to_owned(),
+                    Value::String((*string).to_owned()),
+                )]))
+                .unwrap();
+            writer.flush().unwrap();
+        }
+        let mut bytes = writer.into_inner().unwrap();
+        let header = crate::container::Header::parse(&bytes).unwrap();
+        let mut pos = header.len;
+        let mut starts = Vec::new();
+        while let Ok(block) = crate::container::read_block(&bytes, pos, &header) {
+            starts.push(block.start);
+            pos = block.next;
+        }
+        assert_eq!(starts.len(), strings.len());
+        for &block in corrupt {
+            // A negative length for the string
+            bytes[starts[block]] = 0x01;
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_skip_errors_resyncs() {
+        let fields = vec!["n".to_owned()];
+        let bytes = file_of_blocks(&["a", "b", "c"], &[0]);
+        let mut cli = CliService::from_reader(Cursor::new(bytes)).unwrap();
+        assert!(cli.get_fields(&fields, &GetOptions::default()).is_err());
+
+        let options = GetOptions {
+            skip_errors: true,
+            ..GetOptions::default()
+        };
+        let rows: Vec<String> = cli
+            .get_fields(&fields, &options)
+            .unwrap()
+            .iter()
+            .map(|row| row[0].value().to_string())
+            .collect();
+        assert_eq!(rows, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_explain_errors_in_memory() {
+        let fields = vec!["n".to_owned()];
+        let bytes = file_of_blocks(&["a", "b"], &[1]);
+        let mut cli = CliService::from_reader(Cursor::new(bytes)).unwrap();
+        let options = GetOptions {
+            explain_errors: true,
+            ..GetOptions::default()
+        };
+        let err = cli.get_fields(&fields, &options).unwrap_err();
+        assert!(err
+            .chain()
+            .any(|cause| cause.to_string().starts_with("Field `n` of type string")));
+    }
+
+    #[test]
+    fn test_abort_after_errors() {
+        let fields = vec!["n".to_owned()];
+        let bytes = file_of_blocks(&["a", "b", "c", "d"], &[0, 2]);
+        let mut cli = CliService::from_reader(Cursor::new(bytes)).unwrap();
+        let allowing = |errors| GetOptions {
+            abort_after_errors: Some(errors),
+            ..GetOptions::default()
+        };
+        assert!(cli.get_fields(&fields, &allowing(1)).is_err());
+        let rows = cli.get_fields(&fields, &allowing(2)).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1][0].value().to_string(), "d");
+    }
+
+    #[test]
+    fn test_get_appended_fields() {
+        let dir = crate::testing::TempDir::new("get-appended-fields");
+        let path = dir.path().join("growing.avro");
+        let bytes = file_of_blocks(&["a", "b", "c"], &[]);
+        // Blocks of a one-letter string take 20 bytes: counts, data and sync marker
+        let blocks = crate::container::Header::parse(&bytes).unwrap().len;
+        // Up to the middle of the last block, as when it is being written
+        fs::write(&path, &bytes[..blocks + 45]).unwrap();
+
+        let fields = vec!["n".to_owned()];
+        let options = GetOptions::default();
+        let mut cli = CliService::from(path.to_string_lossy().into_owned()).unwrap();
+        let values = |rows: AvroData| -> Vec<String> {
+            rows.iter().map(|row| row[0].value().to_string()).collect()
+        };
+        assert_eq!(
+            values(cli.get_appended_fields(&fields, &options).unwrap()),
+            vec!["a", "b"]
+        );
+        fs::write(&path, &bytes).unwrap();
+        assert_eq!(
+            values(cli.get_appended_fields(&fields, &options).unwrap()),
+            vec!["c"]
+        );
+        assert!(cli
+            .get_appended_fields(&fields, &options)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_get_appended_fields_reports_corrupt_blocks() {
+        let dir = crate::testing::TempDir::new("get-appended-corrupt");
+        let path = dir.path().join("growing.avro");
+        fs::write(&path, file_of_blocks(&["a", "b", "c"], &[1])).unwrap();
+
+        let fields = vec!["n".to_owned()];
+        let mut cli = CliService::from(path.to_string_lossy().into_owned()).unwrap();
+        // Only a truncated end is waited on, a damaged block is an error as in other reads
+        assert!(cli
+            .get_appended_fields(&fields, &GetOptions::default())
+            .is_err());
+
+        let mut cli = CliService::from(path.to_string_lossy().into_owned()).unwrap();
+        let options = GetOptions {
+            skip_errors: true,
+            ..GetOptions::default()
+        };
+        let rows: Vec<String> = cli
+            .get_appended_fields(&fields, &options)
+            .unwrap()
+            .iter()
+            .map(|row| row[0].value().to_string())
+            .collect();
+        assert_eq!(rows, vec!["a", "c"]);
+    }
+
+    #[test]
+    fn test_field_order_first_seen() {
+        use apache_avro::Writer;
+
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "r", "fields": [
+                {"name": "a", "type": ["null", "int"]},
+                {"name": "b", "type": ["null", "int"]},
+                {"name": "c", "type": ["null", "int"]}
+            ]}"#,
+        )
+        .unwrap();
+        let mut writer = Writer::new(&schema, Vec::new());
+        let null = || Value::Union(0, Box::new(Value::Null));
+        let int = |n| Value::Union(1, Box::new(Value::Int(n)));
+        for (a, b) in [(null(), int(1)), (int(2), null())] {
+            writer
+                .append(Value::Record(vec![
+                    ("a".to_owned(), a),
+                    ("b".to_owned(), b),
+                    ("c".to_owned(), null()),
+                ]))
+                .unwrap();
+        }
+        let bytes = writer.into_inner().unwrap();
+
+        let mut cli = CliService::from_reader(Cursor::new(bytes)).unwrap();
+        assert_eq!(
+            cli.get_field_names_across_files(FieldOrder::Schema)
+                .unwrap(),
+            vec!["a", "b", "c"]
+        );
+        assert_eq!(
+            cli.get_field_names_across_files(FieldOrder::FirstSeen)
+                .unwrap(),
+            vec!["b", "a", "c"]
+        );
+    }
+
+    #[test]
+    fn test_dedupe_by_with_drop_empty() {
+        use apache_avro::Writer;
+
+        let dir = crate::testing::TempDir::new("dedupe-by-with-drop-empty");
+        let write = |name: &str, schema: &str, record: Vec<(&str, &str)>| {
+            let schema = Schema::parse_str(schema).unwrap();
+            let mut writer = Writer::new(&schema, File::create(dir.path().join(name)).unwrap());
+            writer
+                .append(Value::Record(
+                    record
+                        .into_iter()
+                        .map(|(name, value)| (name.to_owned(), Value::String(value.to_owned())))
+                        .collect(),
+                ))
+                .unwrap();
+            writer.flush().unwrap();
+        };
+        write(
+            "a.avro",
+            r#"{"type": "record", "name": "r", "fields": [
+                {"name": "id", "type": "string"},
+                {"name": "other", "type": "string"}
+            ]}"#,
+            vec![("id", "1"), ("other", "x")],
+        );
+        write(
+            "b.avro",
+            r#"{"type": "record", "name": "r", "fields": [
+                {"name": "id", "type": "string"},
+                {"name": "name", "type": "string"}
+            ]}"#,
+            vec![("id", "1"), ("name", "Marty")],
+        );
+
+        let mut cli =
+            CliService::from(dir.path().join("*.avro").to_string_lossy().into_owned()).unwrap();
+        cli.sort_files(FileOrder::Name).unwrap();
+        let options = GetOptions {
+            dedupe_by: vec!["id".to_owned()],
+            drop_empty: true,
+            ..GetOptions::default()
+        };
+        // The record of a.avro has no `name` and is dropped, leaving its key to b.avro's
+        let rows = cli.get_fields(&["name".to_owned()], &options).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0][0].value().to_string(), "Marty");
     }
 }
@@ -0,0 +1,107 @@
+use crate::cli::CliService;
+use crate::container::{read_block, Header, SYNC_LEN};
+use apache_avro::Codec;
+use miette::{bail, miette, Result, WrapErr as _};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Codec to compress the Avro files written by any command with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputCodec {
+    #[default]
+    Null,
+    Deflate,
+    Snappy,
+    Zstd,
+    Bzip2,
+}
+
+impl OutputCodec {
+    /// The codec to give writers, failing when apache-avro is built without support for it
+    pub fn codec(self) -> Result<Codec> {
+        let name = match self {
+            OutputCodec::Null => "null",
+            OutputCodec::Deflate => "deflate",
+            OutputCodec::Snappy => "snappy",
+            OutputCodec::Zstd => "zstandard",
+            OutputCodec::Bzip2 => "bzip2",
+        };
+        Codec::from_str(name).map_err(|_| miette!("Unsupported codec {}", name))
+    }
+}
+
+/// How well a container file's codec compresses its records
+#[derive(Debug, Clone)]
+pub struct CompressionReport {
+    pub path: PathBuf,
+    pub codec: Codec,
+    pub blocks: u64,
+    pub records: u64,
+    /// Size of the whole file, header included
+    pub file_size: u64,
+    /// Size of the blocks' data as stored
+    pub compressed: u64,
+    /// Size of the blocks' data once decompressed, that is of the records' binary encoding
+    pub uncompressed: u64,
+}
+
+impl CompressionReport {
+    /// How many times smaller the stored data is than the records, None for empty files
+    pub fn ratio(&self) -> Option<f64> {
+        (self.compressed > 0).then(|| self.uncompressed as f64 / self.compressed as f64)
+    }
+}
+
+/// Measure the compression of each file, by decompressing every block.
+///
+/// The records aren't decoded, so this is much faster than reading them, but a damaged
+/// block stops the measure: `repair` the file first.
+pub fn measure(avro: &mut CliService) -> Result<Vec<CompressionReport>> {
+    let mut reports = Vec::new();
+    for file in avro.files_mut() {
+        let path = file.path().to_owned();
+        let bytes = file.read_all()?;
+        let header = Header::parse(&bytes)
+            .wrap_err_with(|| format!("Could not read the header of {}", path.display()))?;
+
+        let mut report = CompressionReport {
+            path,
+            codec: header.codec,
+            blocks: 0,
+            records: 0,
+            file_size: bytes.len() as u64,
+            compressed: 0,
+            uncompressed: 0,
+        };
+        let mut pos = header.len;
+        while pos < bytes.len() {
+            let Ok(block) = read_block(&bytes, pos, &header) else {
+                bail!(
+                    "The block at byte {} of {} is damaged",
+                    pos,
+                    report.path.display()
+                );
+            };
+            report.blocks += 1;
+            report.records += block.count;
+            // Block data runs up to the sync marker that ends the block
+            report.compressed += (block.next - block.start - SYNC_LEN) as u64;
+            report.uncompressed += block.data.len() as u64;
+            pos = block.next;
+        }
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+/// The name of a codec as written in container file headers
+pub fn codec_name(codec: Codec) -> &'static str {
+    match codec {
+        Codec::Null => "null",
+        Codec::Deflate => "deflate",
+        Codec::Snappy => "snappy",
+        Codec::Zstandard(_) => "zstandard",
+        Codec::Bzip2(_) => "bzip2",
+        Codec::Xz(_) => "xz",
+    }
+}
@@ -0,0 +1,505 @@
+//! Low-level reading of Avro object container files, block by block, for when the
+//! regular reader gives up on damaged data
+
+use apache_avro::{
+    from_avro_datum, from_avro_datum_schemata, schema::RecordSchema, types::Value, AvroResult,
+    Codec, Reader, Schema,
+};
+use miette::{bail, miette, IntoDiagnostic, Result, WrapErr as _};
+use std::cell::{Cell, RefCell};
+use std::io::{self, BufRead, Cursor, Read, Seek, SeekFrom};
+use std::rc::Rc;
+use std::str::FromStr;
+
+const MAGIC: &[u8] = b"Obj\x01";
+pub(crate) const SYNC_LEN: usize = 16;
+
+pub(crate) struct Header {
+    pub(crate) schema: Schema,
+    /// The schema as written, with annotations the parsed schema may have dropped
+    pub(crate) schema_json: serde_json::Value,
+    pub(crate) codec: Codec,
+    /// Metadata other than the reserved `avro.` keys
+    pub(crate) user_metadata: Vec<(String, Vec<u8>)>,
+    pub(crate) sync: [u8; SYNC_LEN],
+    /// Length of the header in bytes
+    pub(crate) len: usize,
+}
+
+impl Header {
+    pub(crate) fn parse(bytes: &[u8]) -> Result<Self> {
+        if !bytes.starts_with(MAGIC) {
+            bail!("Not an Avro container file");
+        }
+
+        let truncated = || miette!("The header is truncated");
+        let mut pos = MAGIC.len();
+        let mut schema = None;
+        let mut codec = None;
+        let mut user_metadata = Vec::new();
+        loop {
+            let count = read_long(bytes, &mut pos).ok_or_else(truncated)?;
+            if count == 0 {
+                break;
+            }
+            if count < 0 {
+                // Negative counts are followed by the size of the block in bytes
+                read_long(bytes, &mut pos).ok_or_else(truncated)?;
+            }
+            for _ in 0..count.unsigned_abs() {
+                let key = read_bytes(bytes, &mut pos).ok_or_else(truncated)?;
+                let value = read_bytes(bytes, &mut pos).ok_or_else(truncated)?;
+                let key = String::from_utf8_lossy(key).into_owned();
+                match key.as_str() {
+                    "avro.schema" => schema = Some(value),
+                    "avro.codec" => codec = Some(value),
+                    _ if key.starts_with("avro.") => {}
+                    _ => user_metadata.push((key, value.to_vec())),
+                }
+            }
+        }
+
+        let sync = bytes
+            .get(pos..pos + SYNC_LEN)
+            .ok_or_else(truncated)?
+            .try_into()
+            .expect("sync marker has the right length");
+
+        let schema = schema.ok_or_else(|| miette!("The header has no schema"))?;
+        let schema_json = serde_json::from_slice(schema)
+            .into_diagnostic()
+            .wrap_err("Could not parse the schema")?;
+        let schema = Schema::parse_str(&String::from_utf8_lossy(schema))
+            .into_diagnostic()
+            .wrap_err("Could not parse the schema")?;
+        let codec = match codec {
+            None => Codec::Null,
+            Some(codec) => {
+                let codec = String::from_utf8_lossy(codec);
+                Codec::from_str(&codec).map_err(|_| miette!("Unsupported codec {}", codec))?
+            }
+        };
+
+        Ok(Header {
+            schema,
+            schema_json,
+            codec,
+            user_metadata,
+            sync,
+            len: pos + SYNC_LEN,
+        })
+    }
+}
+
+/// A block whose framing is intact, with its data decompressed
+pub(crate) struct Block {
+    /// Number of records the block declares
+    pub(crate) count: u64,
+    /// Offset in the file of the block's data
+    pub(crate) start: usize,
+    pub(crate) data: Vec<u8>,
+    /// Offset in the file of the next block
+    pub(crate) next: usize,
+}
+
+pub(crate) struct BadBlock {
+    /// Number of records the block declares, if it was intact enough to tell
+    pub(crate) declared_records: Option<u64>,
+}
+
+/// Read the block at `pos`, checking its sync marker and decompressing its data
+pub(crate) fn read_block(
+    bytes: &[u8],
+    mut pos: usize,
+    header: &Header,
+) -> std::result::Result<Block, BadBlock> {
+    let unknown = BadBlock {
+        declared_records: None,
+    };
+    let count = read_long(bytes, &mut pos).filter(|count| *count >= 0);
+    let size = read_long(bytes, &mut pos).filter(|size| *size >= 0);
+    let (Some(count), Some(size)) = (count, size) else {
+        return Err(unknown);
+    };
+    let Some(end) = pos
+        .checked_add(size as usize)
+        .filter(|end| end + SYNC_LEN <= bytes.len())
+    else {
+        return Err(unknown);
+    };
+    if bytes[end..end + SYNC_LEN] != header.sync {
+        return Err(unknown);
+    }
+
+    let mut data = bytes[pos..end].to_vec();
+    if header.codec.decompress(&mut data).is_err() {
+        // The block is where it says it is, so its record count can be trusted
+        return Err(BadBlock {
+            declared_records: Some(count as u64),
+        });
+    }
+    Ok(Block {
+        count: count as u64,
+        start: pos,
+        data,
+        next: end + SYNC_LEN,
+    })
+}
+
+/// Where reading a container file can pick up again: the offset of a block, and how many
+/// of its records were already read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockPosition {
+    pub block: u64,
+    pub records: u64,
+}
+
+/// A seekable input to read a container file from
+pub trait Input: BufRead + Seek {}
+
+impl<T: BufRead + Seek> Input for T {}
+
+/// Reads from an input shared with the container reader, keeping track of the offset
+struct Tracked<'a> {
+    input: Rc<RefCell<Box<dyn Input + 'a>>>,
+    offset: Rc<Cell<u64>>,
+}
+
+impl Read for Tracked<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.input.borrow_mut().read(buf)?;
+        self.offset.set(self.offset.get() + read as u64);
+        Ok(read)
+    }
+}
+
+/// Records of an object container file, decoded by apache-avro's reader.
+///
+/// That reader stops at the first block it can't read. This one goes on past the next
+/// sync marker instead, losing the rest of the damaged block only, and can start from any
+/// block. The reader reads whole blocks and nothing more, so the offset reached in the
+/// input tells where each block starts.
+pub struct ContainerReader<'a> {
+    input: Rc<RefCell<Box<dyn Input + 'a>>>,
+    offset: Rc<Cell<u64>>,
+    reader: Option<Reader<'a, Box<dyn Read + 'a>>>,
+    schema: Schema,
+    /// The header as read, to start new readers with
+    header: Vec<u8>,
+    /// The block records are being read from
+    position: BlockPosition,
+    /// Offset of a block that couldn't be read, to look for the next one from
+    failed: Option<u64>,
+}
+
+impl<'a> ContainerReader<'a> {
+    pub fn new(input: Box<dyn Input + 'a>) -> Result<Self> {
+        let input = Rc::new(RefCell::new(input));
+        let offset = Rc::new(Cell::new(0));
+        let tracked: Box<dyn Read + 'a> = Box::new(Tracked {
+            input: input.clone(),
+            offset: offset.clone(),
+        });
+        let reader = Reader::new(tracked).into_diagnostic()?;
+        let schema = reader.writer_schema().clone();
+        let len = offset.get();
+        let mut header = vec![0; len as usize];
+        {
+            let mut input = input.borrow_mut();
+            input
+                .seek(SeekFrom::Start(0))
+                .and_then(|_| input.read_exact(&mut header))
+                .into_diagnostic()
+                .wrap_err("Could not read the header again")?;
+        }
+        Ok(ContainerReader {
+            input,
+            offset,
+            reader: Some(reader),
+            schema,
+            header,
+            position: BlockPosition {
+                block: len,
+                records: 0,
+            },
+            failed: None,
+        })
+    }
+
+    pub fn writer_schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// The header, parsed again for what the reader doesn't keep
+    pub(crate) fn header(&self) -> Result<Header> {
+        Header::parse(&self.header)
+    }
+
+    /// Where the next record is: the block the last one came from and how many records of
+    /// it were read, or the block after it once that one is known to be done with
+    pub fn position(&self) -> BlockPosition {
+        self.position
+    }
+
+    /// Carry on from a position returned by `position`, which must be that of a block
+    pub fn seek(&mut self, position: BlockPosition) -> Result<()> {
+        self.start_at(position.block).into_diagnostic()?;
+        for _ in 0..position.records {
+            match self.next() {
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(err).into_diagnostic(),
+                None => bail!(
+                    "The block at byte {} has fewer records than already read",
+                    position.block
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the blocks from `offset` on with a new reader
+    fn start_at(&mut self, offset: u64) -> io::Result<()> {
+        self.input.borrow_mut().seek(SeekFrom::Start(offset))?;
+        self.offset.set(offset);
+        let blocks = Tracked {
+            input: self.input.clone(),
+            offset: self.offset.clone(),
+        };
+        // The header is read from memory, so the offset is that of the blocks
+        let read: Box<dyn Read + 'a> = Box::new(Cursor::new(self.header.clone()).chain(blocks));
+        self.reader = Some(Reader::new(read).map_err(io::Error::other)?);
+        self.position = BlockPosition {
+            block: offset,
+            records: 0,
+        };
+        Ok(())
+    }
+
+    /// Find the first sync marker after `offset` and read the blocks after it, returning
+    /// whether there was one
+    fn resync(&mut self, offset: u64) -> io::Result<bool> {
+        let sync = &self.header[self.header.len() - SYNC_LEN..];
+        let mut input = self.input.borrow_mut();
+        input.seek(SeekFrom::Start(offset))?;
+        // Scanned bytes, keeping enough at the end for a marker split across reads
+        let mut window = Vec::new();
+        let mut start = offset;
+        let next = loop {
+            let buf = input.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(false);
+            }
+            let len = buf.len();
+            window.extend_from_slice(buf);
+            input.consume(len);
+            if let Some(at) = find(&window, sync) {
+                break start + (at + SYNC_LEN) as u64;
+            }
+            let kept = window.len().min(SYNC_LEN - 1);
+            start += (window.len() - kept) as u64;
+            window.drain(..window.len() - kept);
+        };
+        drop(input);
+        self.start_at(next)?;
+        Ok(true)
+    }
+}
+
+impl Iterator for ContainerReader<'_> {
+    type Item = AvroResult<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(offset) = self.failed.take() {
+            // Unreadable from there on, as when the input can't be read either
+            if !self.resync(offset).unwrap_or(false) {
+                return None;
+            }
+        }
+        let reader = self.reader.as_mut()?;
+        let before = self.offset.get();
+        let record = reader.next();
+        if self.offset.get() != before {
+            // The reader went on to the next block
+            self.position = BlockPosition {
+                block: before,
+                records: 0,
+            };
+        }
+        match record {
+            Some(Ok(record)) => {
+                self.position.records += 1;
+                Some(Ok(record))
+            }
+            Some(Err(err)) => {
+                self.reader = None;
+                self.failed = Some(self.position.block);
+                Some(Err(err))
+            }
+            None => {
+                // Every block was read, so later ones start at the end
+                self.position = BlockPosition {
+                    block: self.offset.get(),
+                    records: 0,
+                };
+                None
+            }
+        }
+    }
+}
+
+/// Find where the record at `index` in a container file fails to decode.
+///
+/// Returns a description of the failing block or field, or `None` if the record decodes
+/// fine or can't be found.
+pub(crate) fn explain_decode_error(bytes: &[u8], index: u64) -> Result<Option<String>> {
+    let header = Header::parse(bytes)?;
+    let mut pos = header.len;
+    let mut first = 0;
+    while pos < bytes.len() {
+        let block = match read_block(bytes, pos, &header) {
+            Ok(block) => block,
+            Err(BadBlock {
+                declared_records: None,
+            }) => {
+                return Ok(Some(format!(
+                    "The block at byte {} is damaged: its size or sync marker is wrong",
+                    pos
+                )))
+            }
+            Err(BadBlock {
+                declared_records: Some(count),
+            }) => {
+                return Ok(Some(format!(
+                "The block of {} records at byte {} could not be decompressed with the {:?} codec",
+                count, pos, header.codec
+            )))
+            }
+        };
+        if index >= first + block.count {
+            first += block.count;
+            pos = block.next;
+            continue;
+        }
+
+        let mut data = &block.data[..];
+        for _ in first..index {
+            if from_avro_datum(&header.schema, &mut data, None).is_err() {
+                return Ok(None);
+            }
+        }
+        let offset = block.data.len() - data.len();
+        let at = |offset: usize| match header.codec {
+            Codec::Null => format!("byte {} of the file", block.start + offset),
+            _ => format!(
+                "byte {} of the decompressed block at byte {}",
+                offset, block.start
+            ),
+        };
+        return Ok(Some(
+            match failing_field(&header.schema, &header.schema, &mut data, None) {
+                Some((path, schema)) => format!(
+                    "Field `{}` of type {} could not be decoded at {}",
+                    path,
+                    crate::schema::type_name(schema),
+                    at(block.data.len() - data.len())
+                ),
+                None => format!("The record starts at {}", at(offset)),
+            },
+        ));
+    }
+    Ok(None)
+}
+
+/// Decode a value field by field, returning the path and schema of the first field that
+/// fails. `data` is left at the start of that field.
+fn failing_field<'s>(
+    root: &Schema,
+    schema: &'s Schema,
+    data: &mut &[u8],
+    parent: Option<&str>,
+) -> Option<(String, &'s Schema)> {
+    let Schema::Record(RecordSchema { fields, .. }) = schema else {
+        return None;
+    };
+    for field in fields {
+        let path = match parent {
+            Some(parent) => format!("{}.{}", parent, field.name),
+            None => field.name.clone(),
+        };
+        let before = *data;
+        if from_avro_datum_schemata(&field.schema, vec![root], data, None).is_err() {
+            *data = before;
+            return failing_field(root, &field.schema, data, Some(&path))
+                .or(Some((path, &field.schema)));
+        }
+    }
+    None
+}
+
+/// Read a zig-zag encoded variable-length long
+fn read_long(bytes: &[u8], pos: &mut usize) -> Option<i64> {
+    let mut value: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some((value >> 1) as i64 ^ -((value & 1) as i64));
+        }
+    }
+    None
+}
+
+fn read_bytes<'b>(bytes: &'b [u8], pos: &mut usize) -> Option<&'b [u8]> {
+    let len = usize::try_from(read_long(bytes, pos)?).ok()?;
+    let value = bytes.get(*pos..pos.checked_add(len)?)?;
+    *pos += len;
+    Some(value)
+}
+
+pub(crate) fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Format bytes as lines of an offset, 16 bytes in hex and the same bytes as ASCII,
+/// like `hexdump -C`
+pub(crate) fn hex_dump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let mut hex = String::new();
+        for (i, byte) in chunk.iter().enumerate() {
+            // An extra space halfway makes offsets easier to count
+            hex.push_str(if i == 8 { "  " } else { " " });
+            hex.push_str(&format!("{:02x}", byte));
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        dump.push_str(&format!("{:08x} {:<49}  |{}|\n", line * 16, hex, ascii));
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_dump() {
+        let dump = hex_dump(b"Obj\x01\x04\x14avro.codec\x08null");
+        assert_eq!(
+            dump,
+            "00000000  4f 62 6a 01 04 14 61 76  72 6f 2e 63 6f 64 65 63  |Obj...avro.codec|\n\
+             00000010  08 6e 75 6c 6c                                    |.null|\n"
+        );
+    }
+}
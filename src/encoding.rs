@@ -0,0 +1,130 @@
+use encoding_rs::{Encoder, EncoderResult, Encoding, UTF_8, WINDOWS_1252};
+use miette::{bail, Result};
+use std::io::{self, ErrorKind, Write};
+
+/// Character encoding of text output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum OutputEncoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1, written as its common superset windows-1252
+    Latin1,
+}
+
+impl OutputEncoding {
+    fn encoding(self) -> &'static Encoding {
+        match self {
+            Self::Utf8 => UTF_8,
+            Self::Latin1 => WINDOWS_1252,
+        }
+    }
+}
+
+/// Writer that transcodes the UTF-8 text written to it into another encoding
+pub(crate) struct Transcoder<W: Write> {
+    inner: W,
+    encoder: Encoder,
+    /// Encoded text written in place of characters the encoding can't represent,
+    /// or None to fail on them
+    substitute: Option<Vec<u8>>,
+    /// The start of a character split across writes
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Transcoder<W> {
+    pub(crate) fn new(
+        inner: W,
+        encoding: OutputEncoding,
+        substitute: Option<&str>,
+    ) -> Result<Self> {
+        let encoding = encoding.encoding();
+        let substitute = match substitute {
+            None => None,
+            Some(substitute) => {
+                let (encoded, _, unmappable) = encoding.encode(substitute);
+                if unmappable {
+                    bail!(
+                        "The substitute `{}` can't be written in {}",
+                        substitute,
+                        encoding.name()
+                    );
+                }
+                Some(encoded.into_owned())
+            }
+        };
+        Ok(Transcoder {
+            inner,
+            encoder: encoding.new_encoder(),
+            substitute,
+            pending: Vec::new(),
+        })
+    }
+
+    fn encode(&mut self, mut text: &str) -> io::Result<()> {
+        let mut buffer = [0; 4096];
+        loop {
+            let (result, read, written) =
+                self.encoder
+                    .encode_from_utf8_without_replacement(text, &mut buffer, false);
+            self.inner.write_all(&buffer[..written])?;
+            text = &text[read..];
+            match result {
+                EncoderResult::InputEmpty => return Ok(()),
+                EncoderResult::OutputFull => {}
+                EncoderResult::Unmappable(c) => match &self.substitute {
+                    Some(substitute) => self.inner.write_all(substitute)?,
+                    None => {
+                        return Err(io::Error::new(
+                            ErrorKind::InvalidData,
+                            format!(
+                                "`{}` can't be written in {}",
+                                c,
+                                self.encoder.encoding().name()
+                            ),
+                        ))
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl<W: Write> Write for Transcoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            // Incomplete at the end: the rest of the character comes with the next write
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(err) => return Err(io::Error::new(ErrorKind::InvalidData, err)),
+        };
+        let pending = std::mem::take(&mut self.pending);
+        let (text, rest) = pending.split_at(valid);
+        self.encode(std::str::from_utf8(text).expect("checked to be valid UTF-8"))?;
+        self.pending = rest.to_vec();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcoder() {
+        let mut out = Vec::new();
+        let mut transcoder = Transcoder::new(&mut out, OutputEncoding::Latin1, Some("?")).unwrap();
+        let text = "café → ok".as_bytes();
+        // Split in the middle of `é`
+        transcoder.write_all(&text[..4]).unwrap();
+        transcoder.write_all(&text[4..]).unwrap();
+        assert_eq!(out, b"caf\xe9 ? ok");
+
+        let mut strict = Transcoder::new(Vec::new(), OutputEncoding::Latin1, None).unwrap();
+        assert!(strict.write_all("→".as_bytes()).is_err());
+    }
+}
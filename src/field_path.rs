@@ -0,0 +1,76 @@
+//! Paths to values nested in records, maps and arrays, like `items[2].name`
+
+use std::iter;
+
+/// A step along a field path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment<'a> {
+    /// A field of a record, or the key of a map entry
+    Field(&'a str),
+    /// An element of an array, counting from the end when negative
+    Index(i64),
+}
+
+/// Split a path like `a.b[3].c` into its segments: names separated by dots, each followed
+/// by any array indices in brackets. The first segment is always the name of a field.
+///
+/// Brackets that don't hold a number are kept as part of the name, so every path parses.
+pub fn parse(path: &str) -> Vec<Segment<'_>> {
+    path.split('.')
+        .flat_map(|part| {
+            let (name, indices) = parse_indices(part);
+            iter::once(Segment::Field(name)).chain(indices.into_iter().map(Segment::Index))
+        })
+        .collect()
+}
+
+/// Split a name like `tags[0][-1]` into the name and its array indices.
+/// A name without a valid index suffix is returned whole, with no indices.
+fn parse_indices(field: &str) -> (&str, Vec<i64>) {
+    let mut name = field;
+    let mut indices = Vec::new();
+    while let Some((head, index)) = name
+        .strip_suffix(']')
+        .and_then(|rest| rest.rsplit_once('['))
+    {
+        match index.trim().parse() {
+            Ok(index) if !head.is_empty() => {
+                indices.push(index);
+                name = head;
+            }
+            _ => break,
+        }
+    }
+    indices.reverse();
+    (name, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_indices() {
+        assert_eq!(parse_indices("tags"), ("tags", vec![]));
+        assert_eq!(parse_indices("tags[0]"), ("tags", vec![0]));
+        assert_eq!(parse_indices("tags[-1][2]"), ("tags", vec![-1, 2]));
+        assert_eq!(parse_indices("tags[x]"), ("tags[x]", vec![]));
+        assert_eq!(parse_indices("[0]"), ("[0]", vec![]));
+    }
+
+    #[test]
+    fn test_parse() {
+        use Segment::{Field, Index};
+
+        assert_eq!(parse("name"), vec![Field("name")]);
+        assert_eq!(
+            parse("a.b[3].c"),
+            vec![Field("a"), Field("b"), Index(3), Field("c")]
+        );
+        assert_eq!(
+            parse("items[2][-1].name"),
+            vec![Field("items"), Index(2), Index(-1), Field("name")]
+        );
+        assert_eq!(parse("tags[x].y"), vec![Field("tags[x]"), Field("y")]);
+    }
+}
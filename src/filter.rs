@@ -0,0 +1,168 @@
+//! Record predicates written as expressions, like `age > 18 && starts_with(name, "A")`
+
+use crate::avro_value::{format_value, FormatOptions};
+use apache_avro::types::Value;
+use evalexpr::{Context, EvalexprError, EvalexprResult, Node};
+use miette::{IntoDiagnostic, Result, WrapErr as _};
+use std::collections::HashMap;
+
+/// An expression evaluated against each record, keeping the records it is true for.
+///
+/// Fields are variables: numbers, dates and timestamps are integers or floats, strings and
+/// enums are strings, arrays are tuples, and null or missing fields are `()`. Other values
+/// are strings rendered as in tables.
+///
+/// On top of the operators and built-in functions of
+/// [evalexpr](https://docs.rs/evalexpr/11), strings have `starts_with(s, prefix)`,
+/// `ends_with(s, suffix)`, `contains(s, part)`, `lower(s)` and `upper(s)`, and
+/// `is_null(x)` tells whether a field is null or missing.
+#[derive(Debug, Clone)]
+pub struct RecordFilter {
+    expression: String,
+    tree: Node,
+    /// The fields the expression reads, so only those are converted for each record
+    fields: Vec<String>,
+}
+
+impl RecordFilter {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let tree = evalexpr::build_operator_tree(expression)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Invalid filter expression `{}`", expression))?;
+        let mut fields: Vec<String> = Vec::new();
+        for field in tree.iter_read_variable_identifiers() {
+            if !fields.iter().any(|known| known == field) {
+                fields.push(field.to_owned());
+            }
+        }
+        Ok(RecordFilter {
+            expression: expression.to_owned(),
+            tree,
+            fields,
+        })
+    }
+
+    /// Whether the expression is true for a record. Fails when it doesn't evaluate to a
+    /// boolean, e.g. when it compares a null field with a number
+    pub fn matches(&self, fields: &[(String, Value)]) -> Result<bool> {
+        let mut variables = HashMap::with_capacity(self.fields.len());
+        for wanted in &self.fields {
+            let value = match fields.iter().find(|(name, _)| name == wanted) {
+                Some((_, value)) => to_variable(value)?,
+                None => evalexpr::Value::Empty,
+            };
+            variables.insert(wanted.clone(), value);
+        }
+        self.tree
+            .eval_boolean_with_context(&RecordContext { variables })
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Could not evaluate filter `{}`", self.expression))
+    }
+}
+
+fn to_variable(value: &Value) -> Result<evalexpr::Value> {
+    Ok(match value {
+        Value::Null => evalexpr::Value::Empty,
+        Value::Union(_, value) => to_variable(value)?,
+        Value::Boolean(b) => evalexpr::Value::Boolean(*b),
+        Value::Int(i) | Value::Date(i) | Value::TimeMillis(i) => evalexpr::Value::Int((*i).into()),
+        Value::Long(l)
+        | Value::TimeMicros(l)
+        | Value::TimestampMillis(l)
+        | Value::TimestampMicros(l)
+        | Value::TimestampNanos(l)
+        | Value::LocalTimestampMillis(l)
+        | Value::LocalTimestampMicros(l)
+        | Value::LocalTimestampNanos(l) => evalexpr::Value::Int(*l),
+        Value::Float(f) => evalexpr::Value::Float((*f).into()),
+        Value::Double(d) => evalexpr::Value::Float(*d),
+        Value::String(s) | Value::Enum(_, s) => evalexpr::Value::String(s.clone()),
+        Value::Array(items) => {
+            evalexpr::Value::Tuple(items.iter().map(to_variable).collect::<Result<_>>()?)
+        }
+        Value::Decimal(_) | Value::BigDecimal(_) => {
+            let options = FormatOptions {
+                decimal_as_float: true,
+                ..FormatOptions::default()
+            };
+            let text = format_value(value, &options)?;
+            match text.parse() {
+                Ok(number) => evalexpr::Value::Float(number),
+                Err(_) => evalexpr::Value::String(text),
+            }
+        }
+        other => evalexpr::Value::String(format_value(other, &FormatOptions::default())?),
+    })
+}
+
+/// The fields of one record, with the string helpers
+struct RecordContext {
+    variables: HashMap<String, evalexpr::Value>,
+}
+
+impl Context for RecordContext {
+    fn get_value(&self, identifier: &str) -> Option<&evalexpr::Value> {
+        self.variables.get(identifier)
+    }
+
+    fn call_function(
+        &self,
+        identifier: &str,
+        argument: &evalexpr::Value,
+    ) -> EvalexprResult<evalexpr::Value> {
+        let strings = |argument: &evalexpr::Value| -> EvalexprResult<(String, String)> {
+            let arguments = argument.as_fixed_len_tuple(2)?;
+            Ok((arguments[0].as_string()?, arguments[1].as_string()?))
+        };
+        Ok(match identifier {
+            "starts_with" => {
+                let (s, prefix) = strings(argument)?;
+                s.starts_with(&prefix).into()
+            }
+            "ends_with" => {
+                let (s, suffix) = strings(argument)?;
+                s.ends_with(&suffix).into()
+            }
+            // `contains` is also a built-in for tuples, which handles everything but strings
+            "contains" => match strings(argument) {
+                Ok((s, part)) => s.contains(&part).into(),
+                Err(_) => return Err(EvalexprError::FunctionIdentifierNotFound(identifier.into())),
+            },
+            "lower" => argument.as_string()?.to_lowercase().into(),
+            "upper" => argument.as_string()?.to_uppercase().into(),
+            "is_null" => argument.is_empty().into(),
+            _ => return Err(EvalexprError::FunctionIdentifierNotFound(identifier.into())),
+        })
+    }
+
+    fn are_builtin_functions_disabled(&self) -> bool {
+        false
+    }
+
+    fn set_builtin_functions_disabled(&mut self, _disabled: bool) -> EvalexprResult<()> {
+        Err(EvalexprError::ContextNotMutable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_filter() {
+        let record = vec![
+            ("name".to_owned(), Value::String("Alice".to_owned())),
+            ("age".to_owned(), Value::Union(1, Box::new(Value::Int(30)))),
+            ("nickname".to_owned(), Value::Null),
+        ];
+        let matches = |expression: &str| RecordFilter::parse(expression).unwrap().matches(&record);
+
+        assert!(matches(r#"age > 18 && starts_with(name, "A")"#).unwrap());
+        assert!(!matches(r#"contains(lower(name), "bob")"#).unwrap());
+        assert!(matches("is_null(nickname) && is_null(missing)").unwrap());
+        assert!(matches("contains((1, 2), 2)").unwrap());
+        assert!(matches("nickname > 3").is_err());
+        assert!(matches("age + 1").is_err());
+        assert!(RecordFilter::parse("(age > 18").is_err());
+    }
+}
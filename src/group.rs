@@ -0,0 +1,131 @@
+use crate::avro_value::AvroValue;
+use crate::cli::{AvroColumnarValue, AvroData, CliService, GetOptions};
+use crate::sort::{sort_rows, SortKey};
+use apache_avro::types::Value;
+use miette::Result;
+use std::collections::HashMap;
+
+/// Name of the column holding the number of records in each group
+pub const COUNT_COLUMN: &str = "_count";
+
+/// Order of the groups
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum GroupSort {
+    /// Largest groups first, so the most common values come first
+    #[default]
+    CountDesc,
+    /// Smallest groups first
+    CountAsc,
+    /// By the values of the fields grouped by, in order
+    Key,
+}
+
+/// Count the records for each distinct combination of values of the `by` fields.
+///
+/// Each row has the values of the `by` fields then the count, in a column named
+/// [`COUNT_COLUMN`]. Groups with the same count are ordered by key.
+pub fn group_by(
+    avro: &mut CliService,
+    by: &[String],
+    sort: GroupSort,
+    options: &GetOptions,
+) -> Result<AvroData> {
+    group_rows(by, avro.get_fields(by, options)?, sort)
+}
+
+/// Group rows holding the values of the `by` fields, as `group_by` does
+fn group_rows(by: &[String], rows: AvroData, sort: GroupSort) -> Result<AvroData> {
+    let mut groups: Vec<(Vec<AvroColumnarValue>, i64)> = Vec::new();
+    let mut group_indices: HashMap<Vec<String>, usize> = HashMap::new();
+    for row in rows {
+        let key = row.iter().map(|value| value.value().to_string()).collect();
+        let group = *group_indices.entry(key).or_insert_with(|| {
+            groups.push((row, 0));
+            groups.len() - 1
+        });
+        groups[group].1 += 1;
+    }
+
+    let mut data: AvroData = groups
+        .into_iter()
+        .map(|(mut row, count)| {
+            row.push(AvroColumnarValue::from(
+                COUNT_COLUMN.to_owned(),
+                AvroValue::from(Value::Long(count)),
+            ));
+            row
+        })
+        .collect();
+    let columns: Vec<String> = by
+        .iter()
+        .cloned()
+        .chain([COUNT_COLUMN.to_owned()])
+        .collect();
+    let by_key = by.iter().map(|field| SortKey {
+        column: field.clone(),
+        descending: false,
+    });
+    let keys: Vec<SortKey> = match sort {
+        GroupSort::Key => by_key.collect(),
+        GroupSort::CountDesc | GroupSort::CountAsc => [SortKey {
+            column: COUNT_COLUMN.to_owned(),
+            descending: sort == GroupSort::CountDesc,
+        }]
+        .into_iter()
+        .chain(by_key)
+        .collect(),
+    };
+    sort_rows(&columns, &mut data, &keys)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_rows() {
+        let by = vec!["country".to_owned()];
+        let string = |s: &str| AvroValue::from(Value::String(s.to_owned()));
+        let null = || AvroValue::from(Value::Union(0, Box::new(Value::Null)));
+        let rows: AvroData = [
+            string("NZ"),
+            string("NZ"),
+            AvroValue::na(),
+            null(),
+            string("AU"),
+            string("NZ"),
+            null(),
+        ]
+        .into_iter()
+        .map(|value| vec![AvroColumnarValue::from("country".to_owned(), value)])
+        .collect();
+
+        let grouped = |sort| -> Vec<(String, String)> {
+            group_rows(&by, rows.clone(), sort)
+                .unwrap()
+                .iter()
+                .map(|row| (row[0].value().to_string(), row[1].value().to_string()))
+                .collect()
+        };
+        let groups = |groups: &[(&str, &str)]| -> Vec<(String, String)> {
+            groups
+                .iter()
+                .map(|&(key, count)| (key.to_owned(), count.to_owned()))
+                .collect()
+        };
+        // Null and N/A are groups of their own, and sort after other keys
+        assert_eq!(
+            grouped(GroupSort::CountDesc),
+            groups(&[("NZ", "3"), ("null", "2"), ("AU", "1"), ("N/A", "1")])
+        );
+        assert_eq!(
+            grouped(GroupSort::CountAsc),
+            groups(&[("AU", "1"), ("N/A", "1"), ("null", "2"), ("NZ", "3")])
+        );
+        assert_eq!(
+            grouped(GroupSort::Key),
+            groups(&[("AU", "1"), ("NZ", "3"), ("N/A", "1"), ("null", "2")])
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use apache_avro::schema::{
+    ArraySchema, DecimalSchema, EnumSchema, FixedSchema, MapSchema, Name, RecordSchema, Schema,
+};
+use serde_json::{json, Map, Value};
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Convert an Avro schema to a JSON Schema (draft 2020-12) describing the JSON output of `get`.
+///
+/// Named types (records, enums and fixed) are put in `$defs` and referred to with `$ref`,
+/// so recursive types stay finite. Logical types become `format` annotations.
+pub fn from_avro(schema: &Schema) -> Value {
+    let mut defs = Map::new();
+    let root = convert(schema, &mut defs);
+
+    let mut json_schema = Map::new();
+    json_schema.insert("$schema".to_owned(), DRAFT.into());
+    if let Value::Object(root) = root {
+        json_schema.extend(root);
+    }
+    if !defs.is_empty() {
+        json_schema.insert("$defs".to_owned(), Value::Object(defs));
+    }
+    Value::Object(json_schema)
+}
+
+fn convert(schema: &Schema, defs: &mut Map<String, Value>) -> Value {
+    match schema {
+        Schema::Null => json!({ "type": "null" }),
+        Schema::Boolean => json!({ "type": "boolean" }),
+        Schema::Int => json!({ "type": "integer", "minimum": i32::MIN, "maximum": i32::MAX }),
+        Schema::Long => json!({ "type": "integer", "minimum": i64::MIN, "maximum": i64::MAX }),
+        Schema::Float | Schema::Double => json!({ "type": "number" }),
+        Schema::String => json!({ "type": "string" }),
+        // Bytes are output as arrays of octets
+        Schema::Bytes => octets(None),
+        Schema::Array(ArraySchema { items, .. }) => {
+            json!({ "type": "array", "items": convert(items, defs) })
+        }
+        Schema::Map(MapSchema { types, .. }) => {
+            json!({ "type": "object", "additionalProperties": convert(types, defs) })
+        }
+        Schema::Union(union) => json!({
+            "anyOf": union
+                .variants()
+                .iter()
+                .map(|variant| convert(variant, defs))
+                .collect::<Vec<_>>()
+        }),
+        Schema::Record(RecordSchema {
+            name, doc, fields, ..
+        }) => define(name, defs, |defs| {
+            let mut properties = Map::new();
+            let mut required = Vec::new();
+            for field in fields {
+                let mut property = convert(&field.schema, defs);
+                if let Value::Object(property) = &mut property {
+                    if let Some(doc) = &field.doc {
+                        property.insert("description".to_owned(), doc.as_str().into());
+                    }
+                    if let Some(default) = &field.default {
+                        property.insert("default".to_owned(), default.clone());
+                    }
+                }
+                if field.default.is_none() {
+                    required.push(Value::from(field.name.as_str()));
+                }
+                properties.insert(field.name.clone(), property);
+            }
+
+            let mut object = named(name, doc);
+            object.insert("type".to_owned(), "object".into());
+            object.insert("properties".to_owned(), Value::Object(properties));
+            object.insert("required".to_owned(), Value::Array(required));
+            object.insert("additionalProperties".to_owned(), false.into());
+            object
+        }),
+        Schema::Enum(EnumSchema {
+            name, doc, symbols, ..
+        }) => define(name, defs, |_| {
+            let mut object = named(name, doc);
+            object.insert("type".to_owned(), "string".into());
+            object.insert("enum".to_owned(), symbols.clone().into());
+            object
+        }),
+        Schema::Fixed(FixedSchema {
+            name, doc, size, ..
+        }) => define(name, defs, |_| {
+            let mut object = named(name, doc);
+            if let Value::Object(octets) = octets(Some(*size)) {
+                object.extend(octets);
+            }
+            object
+        }),
+        Schema::Ref { name } => reference(name),
+        Schema::Decimal(DecimalSchema {
+            precision, scale, ..
+        }) => json!({
+            "type": "string",
+            "format": "decimal",
+            "description": format!("Decimal with precision {} and scale {}", precision, scale),
+        }),
+        Schema::BigDecimal => json!({ "type": "string", "format": "decimal" }),
+        Schema::Uuid => json!({ "type": "string", "format": "uuid" }),
+        Schema::Date => json!({ "type": "string", "format": "date" }),
+        Schema::TimeMillis | Schema::TimeMicros => json!({ "type": "string", "format": "time" }),
+        Schema::TimestampMillis
+        | Schema::TimestampMicros
+        | Schema::TimestampNanos
+        | Schema::LocalTimestampMillis
+        | Schema::LocalTimestampMicros
+        | Schema::LocalTimestampNanos => json!({ "type": "string", "format": "date-time" }),
+        Schema::Duration => json!({ "type": "string", "format": "duration" }),
+    }
+}
+
+/// Add a named type to `$defs` the first time it is seen, and refer to it
+fn define(
+    name: &Name,
+    defs: &mut Map<String, Value>,
+    build: impl FnOnce(&mut Map<String, Value>) -> Map<String, Value>,
+) -> Value {
+    let key = name.fullname(None);
+    if !defs.contains_key(&key) {
+        // Reserve the name first so recursive references don't define it again
+        defs.insert(key.clone(), Value::Null);
+        let definition = build(defs);
+        defs.insert(key, Value::Object(definition));
+    }
+    reference(name)
+}
+
+fn reference(name: &Name) -> Value {
+    json!({ "$ref": format!("#/$defs/{}", name.fullname(None)) })
+}
+
+fn named(name: &Name, doc: &Option<String>) -> Map<String, Value> {
+    let mut object = Map::new();
+    object.insert("title".to_owned(), name.name.as_str().into());
+    if let Some(doc) = doc {
+        object.insert("description".to_owned(), doc.as_str().into());
+    }
+    object
+}
+
+fn octets(size: Option<usize>) -> Value {
+    let mut object = json!({
+        "type": "array",
+        "items": { "type": "integer", "minimum": 0, "maximum": 255 },
+    });
+    if let (Some(size), Value::Object(object)) = (size, &mut object) {
+        object.insert("minItems".to_owned(), size.into());
+        object.insert("maxItems".to_owned(), size.into());
+    }
+    object
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_avro() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "Node", "namespace": "test", "fields": [
+                {"name": "value", "type": "long"},
+                {"name": "next", "type": ["null", "Node"], "default": null}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            from_avro(&schema),
+            json!({
+                "$schema": DRAFT,
+                "$ref": "#/$defs/test.Node",
+                "$defs": {
+                    "test.Node": {
+                        "title": "Node",
+                        "type": "object",
+                        "properties": {
+                            "value": { "type": "integer", "minimum": i64::MIN, "maximum": i64::MAX },
+                            "next": {
+                                "anyOf": [{ "type": "null" }, { "$ref": "#/$defs/test.Node" }],
+                                "default": null,
+                            },
+                        },
+                        "required": ["value"],
+                        "additionalProperties": false,
+                    },
+                },
+            })
+        );
+    }
+}
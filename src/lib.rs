@@ -0,0 +1,67 @@
+//! Explore [Apache Avro](https://avro.apache.org/) files: read records across many files,
+//! pick out some of their fields, and render the values as text or JSON.
+//!
+//! ```no_run
+//! # fn main() -> miette::Result<()> {
+//! let fields = vec!["firstName".to_owned(), "age".to_owned()];
+//! let data = explore_avro::explore("test*.avro", &fields, &Default::default())?;
+//! for row in data {
+//!     for value in row {
+//!         println!("{}: {}", value.name(), value.value());
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use miette::Result;
+
+#[cfg(feature = "archive")]
+mod archive;
+pub mod audit;
+mod avro_value;
+mod cli;
+pub mod compression;
+mod container;
+pub mod field_path;
+pub mod filter;
+pub mod group;
+pub mod json_schema;
+pub mod pivot;
+pub mod repair;
+pub mod schema;
+pub mod sort;
+pub mod split;
+pub mod stats;
+pub mod template;
+#[cfg(test)]
+mod testing;
+
+pub use avro_value::{
+    format_value, json5_object, to_json, to_json5, AvroValue, FormatOptions, NumberLocale,
+    TimestampFormat, NA, NULL,
+};
+pub use cli::{
+    exclude_fields, expand_glob, flatten_all, AvroColumnarValue, AvroData, AvroFile, CliService,
+    FieldCoercion, FieldOrder, FileOrder, GetOptions, GlobMatch, IndexRange, InputFormat,
+    NullReplacement, PathKind, ReadMode, Records, TimeRange, UnionBranch, DEFAULT_BUFFER_CAPACITY,
+};
+pub use container::{BlockPosition, ContainerReader};
+
+/// Get fields from every record of the Avro files matching a glob
+///
+/// # Arguments
+/// * `path` - A glob to match against Avro files
+/// * `fields` - Names of the fields to get, or all top-level fields if empty
+/// * `options` - Which records to read and how to match field names
+pub fn explore(path: &str, fields: &[String], options: &GetOptions) -> Result<AvroData> {
+    let mut avro = CliService::from(path.to_owned())?;
+    let fields = if fields.is_empty() {
+        avro.get_all_field_names()?
+    } else if options.fields_ci {
+        avro.resolve_field_names_ci(fields.to_vec())?
+    } else {
+        fields.to_vec()
+    };
+    avro.get_fields(&fields, options)
+}
@@ -1,126 +1,1918 @@
-use std::io::Write;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, IsTerminal as _, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use avro_value::AvroValue;
-use clap::Parser;
-use cli::{AvroColumnarValue, AvroData, CliService};
+use apache_avro::{types::Value, Codec, Schema, Writer};
+use clap::{ArgAction, Parser, Subcommand};
+use explore_avro::audit::Presence;
+use explore_avro::compression::OutputCodec;
+use explore_avro::filter::RecordFilter;
+use explore_avro::group::GroupSort;
+use explore_avro::sort::{sort_rows, SortKey};
+use explore_avro::stats::{Aggregate, NullHandling};
+use explore_avro::{
+    audit, compression, exclude_fields, expand_glob, flatten_all, group, json5_object, json_schema,
+    pivot, repair, schema, split, stats, template, AvroColumnarValue, AvroData, AvroValue,
+    CliService, FieldCoercion, FieldOrder, FileOrder, FormatOptions, GetOptions, IndexRange,
+    InputFormat, NullReplacement, NumberLocale, ReadMode, TimeRange, TimestampFormat, UnionBranch,
+    DEFAULT_BUFFER_CAPACITY, NA, NULL,
+};
 use miette::{bail, IntoDiagnostic as _, Result, WrapErr as _};
-use prettytable::{color, Attr, Cell, Row, Table};
+use prettytable::{color, format::Alignment, Attr, Cell, Row, Table};
+use rand::{rngs::StdRng, seq::SliceRandom as _, SeedableRng as _};
 use regex::Regex;
+use tracing::{debug, Level};
 
-mod avro_value;
-mod cli;
+mod encoding;
+mod pager;
+mod repl;
+
+use encoding::{OutputEncoding, Transcoder};
+
+/// Number of columns past which `--flatten-all` warns
+const FLATTEN_ALL_WARN_COLUMNS: usize = 500;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
 
 /// A CLI for exploring [Apache Avro](https://avro.apache.org/) files.
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
+struct Args {
+    /// Log what is read to stderr: `-v` for files, codecs and record counts, `-vv` for more
+    #[arg(short, long = "verbose", action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    #[command(subcommand)]
+    command: RavroArgs,
+}
+
+#[derive(Subcommand, Debug)]
+#[allow(clippy::large_enum_variant)]
 enum RavroArgs {
     /// Get fields from an Avro file
     Get {
         /// Files to process
         path: String,
 
-        /// Names of the fields to get to get
+        /// Size in bytes of the read buffer for each file
+        #[arg(long = "buffer-capacity", default_value_t = DEFAULT_BUFFER_CAPACITY)]
+        buffer_capacity: usize,
+
+        /// How to read each file: `stream` it through the read buffer, or read it `whole` into
+        /// memory first
+        #[arg(long = "read-mode", value_enum, default_value_t = ReadMode::Stream)]
+        read_mode: ReadMode,
+
+        /// When a file can't be opened as Avro, print its first N bytes as a hex dump to
+        /// stderr, to spot a wrong magic number, a compression wrapper or a truncated file
+        #[arg(long = "first-n-bytes")]
+        first_n_bytes: Option<usize>,
+
+        /// Order to read the files in: by `name`, modification time (`mtime`) or `size`
+        #[arg(long = "sort-files", value_enum, default_value_t = FileOrder::Name)]
+        sort_files: FileOrder,
+
+        /// How the files are laid out: Avro object `container` files, or a `datum-stream`
+        /// of records with no header, read with `--assume-schema` or `--schema-fd`
+        #[arg(long = "input-format", value_enum, default_value_t = InputFormat::Container)]
+        input_format: InputFormat,
+
+        /// Schema file (`.avsc`) of the records of a `datum-stream`, or `-` to read it from stdin
+        #[arg(long = "assume-schema")]
+        assume_schema: Option<PathBuf>,
+
+        /// Read the schema of the records of a `datum-stream` from this open file descriptor,
+        /// e.g. `--schema-fd 3` with `3< record.avsc`
+        #[arg(long = "schema-fd", conflicts_with = "assume_schema")]
+        schema_fd: Option<u32>,
+
+        /// Follow symbolic links when matching files. This is the default, so this only undoes
+        /// an earlier `--no-follow-symlinks`, e.g. one from a shell alias
+        #[arg(long = "follow-symlinks", overrides_with = "no_follow_symlinks")]
+        follow_symlinks: bool,
+
+        /// Leave out matches that are symbolic links or go through a symlinked directory
+        #[arg(long = "no-follow-symlinks", overrides_with = "follow_symlinks")]
+        no_follow_symlinks: bool,
+
+        /// Print the files the path matches to stderr, whether each is a regular file or a
+        /// symlink, and why any are left out, then carry on
+        #[arg(long = "explain-glob")]
+        explain_glob: bool,
+
+        /// Names of the fields to get to get. Get nested fields with a dotted path, e.g.
+        /// `address.city`, and pick an element of an array with `[n]`, e.g. `tags[-1]` or
+        /// `items[2].name`
         #[arg(short, long = "fields")]
         fields_to_get: Vec<String>,
 
-        /// Regex to search. Only a row with a matching field will appear in the outputted table
+        /// Without `--fields`, get the fields of every file rather than only those of the first
+        #[arg(long = "scan-all")]
+        scan_all: bool,
+
+        /// How to order the fields gathered by `--scan-all`: as the schemas declare them, or by
+        /// the first record they have a value other than null in
+        #[arg(long = "order", value_enum, default_value_t = FieldOrder::Schema, requires = "scan_all")]
+        order: FieldOrder,
+
+        /// Without `--fields`, expand nested records and maps into one dotted column per
+        /// leaf, e.g. `address.city`, gathering the columns of every record
+        #[arg(long = "flatten-all", conflicts_with_all = ["fields_to_get", "watch"])]
+        flatten_all: bool,
+
+        /// With `--flatten-all`, also expand arrays of at most this many records into
+        /// positional columns, e.g. `items.0.name`. Longer arrays are kept in one column
+        #[arg(long = "max-array-cols", requires = "flatten_all")]
+        max_array_cols: Option<usize>,
+
+        /// Match field names regardless of case
+        #[arg(long = "fields-ci")]
+        fields_ci: bool,
+
+        /// Leave out this field, whether it is listed in `--fields` or selected by default.
+        /// Can be given several times
+        #[arg(long = "exclude")]
+        exclude: Vec<String>,
+
+        /// Leave out fields whose name matches this regex, after the others are selected
+        #[arg(long = "exclude-regex")]
+        exclude_regex: Option<String>,
+
+        /// Only keep selected fields of these types, e.g. `string,timestamp-millis`.
+        ///
+        /// Types are primitive or logical type names, or the names of records, enums and
+        /// fixed types. Optional fields match the types of their union's branches, and nested
+        /// fields like `items[2].name` the type their path leads to.
+        #[arg(long = "type-filter", value_delimiter = ',')]
+        type_filter: Vec<String>,
+
+        /// Fail before reading records if a file's schema lacks any of these fields, listing
+        /// the missing ones. Nested fields are written as dotted paths, like `address.city`
+        #[arg(long = "assert-fields", value_delimiter = ',')]
+        assert_fields: Vec<String>,
+
+        /// List the path of every (nested) field in the schema, then exit
+        #[arg(long = "list-paths")]
+        list_paths: bool,
+
+        /// Also write the writer schema of the first file to this path, e.g. `schema.avsc`
+        #[arg(long = "schema-out")]
+        schema_out: Option<PathBuf>,
+
+        /// Regex to search. Only a row with a matching field will appear in the outputted table.
+        ///
+        /// Can be given several times to show rows matching any of the patterns.
         #[arg(short, long = "search")]
-        search: Option<String>,
+        search: Vec<String>,
 
         /// Maximum number of records to show
         #[arg(short, long = "take")]
         take: Option<u32>,
 
+        /// Only get records whose index across all files is in this range.
+        ///
+        /// Written `start..end` (exclusive), `start..=end` (inclusive), or a single index.
+        #[arg(long = "where-index")]
+        where_index: Option<IndexRange>,
+
+        /// Only get records where a union field holds a given branch, written `field=branch`.
+        ///
+        /// The branch is its index in the union, or its type name, like `null` or a record name.
+        #[arg(long = "select-union-branch")]
+        select_union_branch: Option<UnionBranch>,
+
+        /// Only get the first record for each value of these fields, e.g. `--dedupe-by user_id`.
+        ///
+        /// Several fields can be given, separated by commas or by repeating the option.
+        /// `--take` counts the records kept.
+        #[arg(long = "dedupe-by", value_delimiter = ',')]
+        dedupe_by: Vec<String>,
+
+        /// Show a value instead of null or N/A for some fields, e.g. `status=unknown,count=0`.
+        ///
+        /// In JSON, values that parse as the field's type are written as such, like numbers.
+        #[arg(long = "replace-null", value_delimiter = ',')]
+        replace_null: Vec<NullReplacement>,
+
+        /// Convert fields to a type, e.g. `id=long,score=double`, so that every value of a
+        /// column has the same form, like for a strictly typed CSV import.
+        ///
+        /// Types are `long`, `double`, `boolean` and `string`. Values that can't be converted
+        /// are shown as N/A, with a warning for the first one of each field in each file.
+        #[arg(long = "coerce", value_delimiter = ',')]
+        coerce: Vec<FieldCoercion>,
+
+        /// Skip records that have none of the selected fields, rather than output a row of N/A.
+        ///
+        /// Null values count as present. `--take` counts the records kept.
+        #[arg(long = "drop-empty")]
+        drop_empty: bool,
+
+        /// Only get records this expression is true for, e.g. `age > 18 && starts_with(name, "A")`.
+        ///
+        /// Fields are variables, null or missing ones being `()`. Besides the operators and
+        /// functions of evalexpr, there are `starts_with(s, prefix)`, `ends_with(s, suffix)`,
+        /// `contains(s, part)`, `lower(s)`, `upper(s)` and `is_null(x)`. Records the expression
+        /// fails on, e.g. comparing a null field with a number, are errors.
+        #[arg(long = "record-filter-script")]
+        record_filter_script: Option<String>,
+
+        /// Timestamp or date field to filter records by with `--since` and `--until`.
+        ///
+        /// Records where it is null or missing are left out. Local timestamps are taken as UTC.
+        #[arg(long = "time-field")]
+        time_field: Option<String>,
+
+        /// Only get records where the time field is at or after this time.
+        ///
+        /// An RFC 3339 timestamp like `2023-01-01T12:00:00+02:00`, or a date or date and time
+        /// taken as UTC, like `2023-01-01`.
+        #[arg(long = "since", requires = "time_field", value_parser = TimeRange::parse_bound)]
+        since: Option<jiff::Timestamp>,
+
+        /// Only get records where the time field is before this time, written like `--since`
+        #[arg(long = "until", requires = "time_field", value_parser = TimeRange::parse_bound)]
+        until: Option<jiff::Timestamp>,
+
+        /// Output one row per entry of this map or array field, with `_key` and `_value` columns
+        /// in its place.
+        ///
+        /// Other selected fields are repeated on each row. An array's keys are the indices of
+        /// its items. Records where the field is neither a map nor an array keep its value in
+        /// `_value`.
+        #[arg(long = "explode-map", visible_alias = "explode")]
+        explode_map: Option<String>,
+
+        /// Levels of nested maps and arrays to explode, e.g. 2 for an array of arrays.
+        ///
+        /// Each level below the first adds a key column, `_key2`, `_key3`... before `_value`.
+        #[arg(long = "explode-depth", default_value_t = 1, requires = "explode_map")]
+        explode_depth: usize,
+
+        /// Keep at most this many rows exploded from a single record, warning when there are more
+        #[arg(long = "max-exploded-rows", requires = "explode_map")]
+        max_exploded_rows: Option<usize>,
+
+        /// Sort rows by this column, or in descending order with a `-` prefix, e.g. `-age`.
+        ///
+        /// Repeat it to break ties with more columns, e.g. `--sort-by region --sort-by -age`.
+        /// Null and missing values sort last. Only the records read are sorted, so with
+        /// `--take` this sorts the first records, not the whole files.
+        #[arg(long = "sort-by", allow_hyphen_values = true, conflicts_with = "watch")]
+        sort_by: Vec<SortKey>,
+
+        /// Output rows in a random order. All records are read first, then shuffled, and only
+        /// then is `--take` applied, so this takes a random sample of the whole files
+        #[arg(long = "shuffle", conflicts_with_all = ["watch", "sort_by"])]
+        shuffle: bool,
+
+        /// Seed for `--shuffle`, to get the same order on every run
+        #[arg(long = "seed", requires = "shuffle")]
+        seed: Option<u64>,
+
+        /// Maximum number of rows to output, counted after filtering by `--search`
+        #[arg(long = "limit-output-rows")]
+        limit_output_rows: Option<usize>,
+
+        /// Add a `_hash` column with a SHA-256 hash of each whole record, to find duplicates.
+        ///
+        /// Records with the same values hash the same even if their schemas order fields differently.
+        #[arg(long = "hash")]
+        hash: bool,
+
+        /// Add a `_size_bytes` column with the size of each record's Avro encoding, before
+        /// compression. Sort by it with `--sort-by=-_size_bytes` to find the largest records
+        #[arg(long = "with-size")]
+        with_size: bool,
+
+        /// Add a leading `_schema` column naming the schema each record was written with.
+        ///
+        /// Unnamed schemas are identified by their SHA-256 fingerprint instead.
+        #[arg(long = "with-schema-id")]
+        with_schema_id: bool,
+
+        /// Keep running, printing records as they are appended to the files.
+        ///
+        /// Each check reads on from the last block read. Files must use the null or deflate
+        /// codec.
+        #[arg(long = "watch", conflicts_with_all = ["take", "where_index"])]
+        watch: bool,
+
+        /// How often to check for appended records with `--watch`, in milliseconds
+        #[arg(long = "watch-interval", default_value_t = 1000, requires = "watch")]
+        watch_interval: u64,
+
+        /// Warn before reading more than this many bytes when no `--take` is given
+        #[arg(long = "warn-over-bytes", default_value_t = 256 * 1024 * 1024)]
+        warn_over_bytes: u64,
+
+        /// Check enum values against the symbols declared in the schema, warning on mismatches
+        #[arg(long = "enum-validate")]
+        enum_validate: bool,
+
+        /// Show which branch of its union each value holds, as `long=5` in tables and
+        /// `{"long": 5}` in JSON, like Avro's JSON encoding does. Nulls are left as they are
+        #[arg(long = "annotate-unions")]
+        annotate_unions: bool,
+
+        /// Fail instead of warning when a field decodes as the plain base type of the logical
+        /// type the schema declares for it, e.g. a timestamp-millis read as a bare long
+        #[arg(long = "fail-on-schema-warning")]
+        fail_on_schema_warning: bool,
+
+        /// Skip records that can't be decoded or fail validation, instead of stopping.
+        ///
+        /// A record that can't be decoded takes the rest of its block with it, as where the
+        /// next record starts is lost: reading carries on from the next block, or stops for
+        /// files of bare datums, which have no blocks.
+        #[arg(long = "skip-errors")]
+        skip_errors: bool,
+
+        /// Skip up to this many records that can't be decoded or fail validation, then stop
+        /// with an error, to catch files that are corrupt throughout.
+        ///
+        /// Like with --skip-errors, a record that can't be decoded is skipped with the rest of
+        /// its block, and counts as one error.
+        #[arg(long = "abort-after-errors")]
+        abort_after_errors: Option<u64>,
+
+        /// When a record can't be decoded, find the field at fault, its type and byte offset.
+        ///
+        /// This reads the file again block by block, so it is only done once an error happens.
+        #[arg(long = "explain-errors")]
+        explain_errors: bool,
+
+        /// After processing, print the number of records and bytes read and the throughput to stderr
+        #[arg(long = "report-timing")]
+        report_timing: bool,
+
+        /// Treat strings containing the U+FFFD replacement character as errors, to catch mojibake.
+        ///
+        /// Such records stop processing, or are skipped with `--skip-errors`.
+        #[arg(long = "strict-utf8")]
+        strict_utf8: bool,
+
+        /// Don't warn about reading large files
+        #[arg(short, long = "yes", visible_alias = "force")]
+        yes: bool,
+
         /// Output format.
         ///
-        /// Omit for pretty table output, or specify: `csv`, `json`, `json-pretty`.
+        /// Omit for pretty table output, or specify: `csv`, `tsv`, `json`, `json-pretty`,
+        /// `ndjson`, `json5`, `toml`, `avro`.
+        /// `json` writes all records as a single JSON array on one line, and `json-pretty` as an
+        /// indented array, while `ndjson` writes one JSON object per line, so it can be streamed.
+        /// Avro output keeps the schema of the selected fields, and only supports whole
+        /// top-level fields.
         #[arg(short = 'p', long = "format")]
         output_format: Option<String>,
+
+        /// File to write output to, instead of stdout. Tables are written without colours
+        #[arg(long = "output", conflicts_with = "watch")]
+        output: Option<PathBuf>,
+
+        /// Add to the end of the `--output` file instead of replacing it. CSV and TSV headers
+        /// are left out when the file already has content
+        #[arg(long = "append", requires = "output")]
+        append: bool,
+
+        /// Codec to compress avro output with
+        #[arg(long = "codec", value_enum)]
+        codec: Option<OutputCodec>,
+
+        /// Write one output per input file into this directory, instead of a single one.
+        ///
+        /// Each output is named after its input, e.g. `out/2023-01.csv` for `2023-01.avro`.
+        /// Options that limit records, like `--take` and `--where-index`, apply to each file.
+        #[arg(
+            long = "output-dir",
+            requires = "output_format",
+            conflicts_with_all = ["output", "watch", "flatten_all"]
+        )]
+        output_dir: Option<PathBuf>,
+
+        /// Extension of the files written by `--output-dir`. Defaults to one for the format
+        #[arg(long = "output-ext", requires = "output_dir")]
+        output_ext: Option<String>,
+
+        /// Start CSV or JSON output with a UTF-8 byte order mark, for Excel and other Windows tools
+        #[arg(long = "bom", requires = "output_format")]
+        bom: bool,
+
+        /// Character encoding of CSV, JSON, values-only and template output: `utf8` or `latin1`
+        #[arg(long = "output-encoding", value_enum, default_value_t)]
+        output_encoding: OutputEncoding,
+
+        /// Text written in place of characters the output encoding can't represent
+        #[arg(long = "encoding-substitute", default_value = "?")]
+        encoding_substitute: String,
+
+        /// Fail on characters the output encoding can't represent, instead of substituting them
+        #[arg(long = "strict-encoding", conflicts_with = "encoding_substitute")]
+        strict_encoding: bool,
+
+        /// Show records, maps and arrays nested deeper than this as `{...}` or `[...]`.
+        ///
+        /// Applies to table, CSV and values-only output; JSON is always complete.
+        #[arg(long = "max-depth")]
+        max_depth: Option<usize>,
+
+        /// Show decimals as floating point numbers, for quick scanning.
+        ///
+        /// This is lossy past about 15 significant digits: don't use it for exact figures.
+        /// Applies to table, CSV and values-only output; JSON keeps full precision.
+        #[arg(long = "decimal-as-float")]
+        decimal_as_float: bool,
+
+        /// Show fixed values as text when they are valid UTF-8, and as hex otherwise.
+        ///
+        /// Applies to all output formats.
+        #[arg(long = "fixed-as-string")]
+        fixed_as_string: bool,
+
+        /// Group the digits of ints, longs and decimals with thousands separators, e.g. `1,234,567`.
+        ///
+        /// Applies to table, CSV and values-only output; JSON is never grouped.
+        #[arg(long = "group-digits")]
+        group_digits: bool,
+
+        /// Separator style for `--group-digits`
+        #[arg(
+            long = "locale",
+            value_enum,
+            default_value_t,
+            requires = "group_digits"
+        )]
+        locale: NumberLocale,
+
+        /// Show every timestamp the same way whatever its precision: `iso` (RFC 3339),
+        /// `epoch-ms` or `epoch-s` (integers since the Unix epoch).
+        ///
+        /// Local timestamps are taken as UTC. Applies to all output formats.
+        #[arg(long = "normalize-timestamps", value_enum)]
+        normalize_timestamps: Option<TimestampFormat>,
+
+        /// In JSON, write longs too large for JavaScript to hold exactly (past 2^53 - 1) as
+        /// strings, so they keep their exact value. Other numbers stay numbers
+        #[arg(long = "longs-as-strings")]
+        longs_as_strings: bool,
+
+        /// Show table output through a pager, even when stdout is not a terminal.
+        ///
+        /// By default the pager is used when stdout is a terminal. It is taken from
+        /// `RAVRO_PAGER`, then `PAGER`, falling back to `less`.
+        #[arg(long = "pager", conflicts_with = "no_pager")]
+        pager: bool,
+
+        /// Never show table output through a pager
+        #[arg(long = "no-pager")]
+        no_pager: bool,
+
+        /// Title to print above the table. Ignored for other formats
+        #[arg(long = "title")]
+        title: Option<String>,
+
+        /// Wrap table cells at spaces to at most this many characters wide, instead of letting
+        /// long values widen the table. Words longer than that are split
+        #[arg(long = "wrap", value_parser = clap::value_parser!(u16).range(1..))]
+        wrap: Option<u16>,
+
+        /// Alignment of table columns: `left`, `right`, or `auto` to right-align columns
+        /// whose values are all numbers
+        #[arg(long = "align", value_enum, default_value_t = TableAlign::Left)]
+        align: TableAlign,
+
+        /// Colour of the table headers
+        #[arg(long = "header-color", value_enum, default_value_t = TableColor::Blue)]
+        header_color: TableColor,
+
+        /// Colour of table values matching `--search`
+        #[arg(long = "highlight-color", value_enum, default_value_t = TableColor::Green)]
+        highlight_color: TableColor,
+
+        /// Colour of missing (N/A) values in the table
+        #[arg(long = "na-color", value_enum, default_value_t = TableColor::Red)]
+        na_color: TableColor,
+
+        /// Leave out fields whose value is null from JSON and TOML output
+        #[arg(long = "skip-nulls")]
+        skip_nulls: bool,
+
+        /// Output each JSON record as an array of values in field order instead of an object
+        #[arg(long = "json-arrays", conflicts_with = "skip_nulls")]
+        json_arrays: bool,
+
+        /// Exit with an error when there are no records to output
+        #[arg(long = "fail-on-empty")]
+        fail_on_empty: bool,
+
+        /// Print only the values, one record per line, with no headers or decoration
+        #[arg(long = "values-only", conflicts_with = "output_format")]
+        values_only: bool,
+
+        /// Print a line per record from a template with `{field}` placeholders,
+        /// e.g. `user {firstName} is {age}`.
+        ///
+        /// Without `--fields`, the fields in the placeholders are read. Fields a record doesn't
+        /// have are `N/A`. Write `{{` and `}}` for literal braces.
+        #[arg(long = "template", conflicts_with_all = ["output_format", "values_only"])]
+        template: Option<String>,
+
+        /// Separator between values when `--values-only` is used with several fields
+        #[arg(long = "separator", requires = "values_only")]
+        separator: Option<String>,
+    },
+
+    /// Split Avro files into one file per distinct value of a field
+    Split {
+        /// Files to process
+        path: String,
+
+        /// Size in bytes of the read buffer for each file
+        #[arg(long = "buffer-capacity", default_value_t = DEFAULT_BUFFER_CAPACITY)]
+        buffer_capacity: usize,
+
+        /// Name of the field to partition records by
+        #[arg(short, long = "by")]
+        by: String,
+
+        /// Directory to write the partitioned files into
+        #[arg(long = "output-dir")]
+        output_dir: PathBuf,
+
+        /// Maximum number of output files to keep open at once
+        #[arg(long = "max-open-writers", default_value_t = 64)]
+        max_open_writers: usize,
+
+        /// Codec to compress the output files with
+        #[arg(long = "codec", value_enum, default_value_t = OutputCodec::Null)]
+        codec: OutputCodec,
+    },
+
+    /// Aggregate numeric fields: count, sum, mean, minimum, maximum and percentiles
+    Stats {
+        /// Files to process
+        path: String,
+
+        /// Size in bytes of the read buffer for each file
+        #[arg(long = "buffer-capacity", default_value_t = DEFAULT_BUFFER_CAPACITY)]
+        buffer_capacity: usize,
+
+        /// Names of the numeric fields to aggregate
+        #[arg(short, long = "fields", required = true)]
+        fields: Vec<String>,
+
+        /// How to treat null and missing values: leave them out, count them as zero, or fail
+        #[arg(long = "null-handling", value_enum, default_value_t = NullHandling::Skip)]
+        null_handling: NullHandling,
+
+        /// Also compute these percentiles, between 0 and 100, e.g. `50,90,99`.
+        ///
+        /// They are exact, which takes keeping every value in memory: 8 bytes per value
+        /// of each field.
+        #[arg(long = "percentiles", value_delimiter = ',')]
+        percentiles: Vec<f64>,
+    },
+
+    /// Report how often each field is missing or null, to spot always-empty columns
+    Audit {
+        /// Files to process
+        path: String,
+
+        /// Size in bytes of the read buffer for each file
+        #[arg(long = "buffer-capacity", default_value_t = DEFAULT_BUFFER_CAPACITY)]
+        buffer_capacity: usize,
+
+        /// Names of the fields to audit. Defaults to every field of every file
+        #[arg(short, long = "fields")]
+        fields: Vec<String>,
+
+        /// Only audit the first records, e.g. as a sample of large files
+        #[arg(short, long = "take")]
+        take: Option<u32>,
+
+        /// Also show how often each branch of each union field occurs, e.g. to see how
+        /// many nulls, strings and longs an optional field holds
+        #[arg(long = "explain-union-stats")]
+        explain_union_stats: bool,
+    },
+
+    /// Reshape key/value records into a table with one column per distinct key
+    Pivot {
+        /// Files to process
+        path: String,
+
+        /// Size in bytes of the read buffer for each file
+        #[arg(long = "buffer-capacity", default_value_t = DEFAULT_BUFFER_CAPACITY)]
+        buffer_capacity: usize,
+
+        /// Field whose distinct values become the columns
+        #[arg(long = "key-field")]
+        key_field: String,
+
+        /// Field holding the value for each key
+        #[arg(long = "value-field")]
+        value_field: String,
+
+        /// Field to group records into rows by. Without it, all records make a single row
+        #[arg(long = "index-field")]
+        index_field: Option<String>,
+    },
+
+    /// Count the records for each distinct combination of values of some fields
+    GroupBy {
+        /// Files to process
+        path: String,
+
+        /// Size in bytes of the read buffer for each file
+        #[arg(long = "buffer-capacity", default_value_t = DEFAULT_BUFFER_CAPACITY)]
+        buffer_capacity: usize,
+
+        /// Names of the fields to group records by
+        #[arg(short, long = "by", required = true)]
+        by: Vec<String>,
+
+        /// Order of the groups: largest first (`count-desc`), smallest first (`count-asc`),
+        /// or by the values grouped by (`key`)
+        #[arg(long = "group-sort", value_enum, default_value_t = GroupSort::CountDesc)]
+        group_sort: GroupSort,
+    },
+
+    /// Rewrite a damaged Avro file, leaving out the blocks that can't be decoded
+    Repair {
+        /// File to repair
+        path: PathBuf,
+
+        /// File to write the salvaged records to
+        #[arg(long = "output")]
+        output: PathBuf,
+
+        /// Codec to compress the repaired file with. Defaults to the codec of the damaged file
+        #[arg(long = "codec", value_enum)]
+        codec: Option<OutputCodec>,
+    },
+
+    /// Explore files interactively, choosing fields and filters and seeing the records each time
+    Repl {
+        /// Files to process
+        path: String,
+
+        /// Size in bytes of the read buffer for each file
+        #[arg(long = "buffer-capacity", default_value_t = DEFAULT_BUFFER_CAPACITY)]
+        buffer_capacity: usize,
+    },
+
+    /// Report how well each file's codec compresses its records
+    Compression {
+        /// Files to process
+        path: String,
+    },
+
+    /// Count the records of each file, and in total
+    Count {
+        /// Files to process
+        path: String,
+
+        /// Size in bytes of the read buffer for each file
+        #[arg(long = "buffer-capacity", default_value_t = DEFAULT_BUFFER_CAPACITY)]
+        buffer_capacity: usize,
+    },
+
+    /// Check that every file has the same schema, listing the files with each one if not
+    SchemaDrift {
+        /// Files to process
+        path: String,
+    },
+
+    /// Print the writer schema of an Avro file, without decoding any records
+    Schema {
+        /// Files to process. Only the schema of the first one is read
+        path: String,
+
+        /// Output format: `json` on a single line (the default), indented `json-pretty`, or
+        /// `canonical` for the Parsing Canonical Form, which leaves out docs, defaults and
+        /// logical types
+        #[arg(short = 'p', long = "format")]
+        output_format: Option<String>,
+    },
+
+    /// Print the schema of an Avro file as a JSON Schema (draft 2020-12)
+    #[command(name = "jsonschema")]
+    JsonSchema {
+        /// Files to process. Only the schema of the first one is read
+        path: String,
     },
 }
 
 fn main() -> Result<()> {
-    match RavroArgs::parse() {
+    let args = Args::parse();
+    init_logging(args.verbose);
+    match args.command {
         RavroArgs::Get {
             fields_to_get,
+            scan_all,
+            order,
+            flatten_all: flatten,
+            max_array_cols,
+            fields_ci,
+            exclude,
+            exclude_regex,
+            type_filter,
+            assert_fields,
+            list_paths,
+            schema_out,
+            path,
+            buffer_capacity,
+            read_mode,
+            first_n_bytes,
+            sort_files,
+            input_format,
+            assume_schema,
+            schema_fd,
+            // Clears `no_follow_symlinks` when given after it, nothing else to do
+            follow_symlinks: _,
+            no_follow_symlinks,
+            explain_glob,
+            search,
+            take,
+            where_index,
+            select_union_branch,
+            dedupe_by,
+            replace_null,
+            coerce,
+            drop_empty,
+            record_filter_script,
+            time_field,
+            since,
+            until,
+            explode_map,
+            explode_depth,
+            max_exploded_rows,
+            sort_by,
+            shuffle,
+            seed,
+            limit_output_rows,
+            hash,
+            with_size,
+            with_schema_id,
+            watch,
+            watch_interval,
+            warn_over_bytes,
+            enum_validate,
+            fail_on_schema_warning,
+            annotate_unions,
+            skip_errors,
+            abort_after_errors,
+            explain_errors,
+            strict_utf8,
+            report_timing,
+            yes,
+            output_format,
+            output,
+            append,
+            codec,
+            output_dir,
+            output_ext,
+            bom,
+            output_encoding,
+            encoding_substitute,
+            strict_encoding,
+            max_depth,
+            decimal_as_float,
+            fixed_as_string,
+            group_digits,
+            locale,
+            normalize_timestamps,
+            longs_as_strings,
+            pager,
+            no_pager,
+            title,
+            wrap,
+            align,
+            header_color,
+            highlight_color,
+            na_color,
+            skip_nulls,
+            json_arrays,
+            fail_on_empty,
+            values_only,
+            separator,
+            template,
+        } => {
+            if path == "-" && assume_schema.as_deref() == Some(Path::new("-")) {
+                bail!("The records and --assume-schema can't both be read from stdin");
+            }
+            if explain_glob {
+                print_glob_explanation(&path, !no_follow_symlinks)?;
+            }
+            let mut avro = CliService::from_glob(path, !no_follow_symlinks)?;
+            avro.set_buffer_capacity(buffer_capacity);
+            avro.set_read_mode(read_mode);
+            if let Some(len) = first_n_bytes {
+                avro.dump_unreadable(len);
+            }
+            // File descriptors are open as files under /dev/fd on Linux and macOS
+            let schema_path = assume_schema
+                .or_else(|| schema_fd.map(|fd| PathBuf::from(format!("/dev/fd/{}", fd))));
+            match (input_format, schema_path) {
+                (InputFormat::DatumStream, Some(schema_path)) => {
+                    avro.assume_schema(read_schema(&schema_path)?);
+                }
+                (InputFormat::DatumStream, None) => {
+                    bail!("--input-format datum-stream needs --assume-schema or --schema-fd")
+                }
+                (InputFormat::Container, Some(_)) => {
+                    bail!("--assume-schema and --schema-fd are only used with --input-format datum-stream")
+                }
+                (InputFormat::Container, None) => {}
+            }
+            avro.sort_files(sort_files)?;
+            if watch {
+                avro.check_watchable()?;
+            }
+            if !assert_fields.is_empty() {
+                let missing = avro.missing_fields(&assert_fields, fields_ci)?;
+                if !missing.is_empty() {
+                    for (path, fields) in &missing {
+                        eprintln!("{} is missing {}", path.display(), fields.join(", "));
+                    }
+                    bail!("Some files don't have every asserted field");
+                }
+            }
+            if list_paths {
+                let mut stdout = stdout();
+                for path in schema::leaf_paths(&avro.writer_schema()?)? {
+                    writeln!(&mut stdout, "{}", path).into_diagnostic()?;
+                }
+                return Ok(());
+            }
+            if let Some(schema_out) = &schema_out {
+                let schema =
+                    serde_json::to_string_pretty(&avro.writer_schema()?).into_diagnostic()?;
+                fs::write(schema_out, schema + "\n")
+                    .into_diagnostic()
+                    .wrap_err_with(|| {
+                        format!("Could not write schema to {}", schema_out.display())
+                    })?;
+            }
+
+            let template = template
+                .map(|template| template::Template::parse(&template))
+                .transpose()?;
+            let fields_to_get = match &template {
+                Some(template) if fields_to_get.is_empty() => template.fields(),
+                _ => fields_to_get,
+            };
+            let fields_to_get = if fields_to_get.is_empty() && scan_all {
+                avro.get_field_names_across_files(order)?
+            } else if fields_to_get.is_empty() {
+                avro.get_all_field_names()?
+            } else if fields_ci {
+                avro.resolve_field_names_ci(fields_to_get)?
+            } else {
+                fields_to_get
+            };
+            let fields_to_get = exclude_fields(fields_to_get, &exclude, fields_ci);
+            let fields_to_get = match exclude_regex {
+                None => fields_to_get,
+                Some(re) => {
+                    let re = Regex::new(&re)
+                        .into_diagnostic()
+                        .wrap_err("Invalid --exclude-regex")?;
+                    debug!("Compiled --exclude-regex {}", re);
+                    fields_to_get
+                        .into_iter()
+                        .filter(|field| !re.is_match(field))
+                        .collect()
+                }
+            };
+            let fields_to_get = if type_filter.is_empty() {
+                fields_to_get
+            } else {
+                let fields_to_get: Vec<String> = schema::fields_of_types(
+                    &avro.writer_schema()?,
+                    &fields_to_get,
+                    &type_filter,
+                    fields_ci,
+                )?
+                .into_iter()
+                .cloned()
+                .collect();
+                if fields_to_get.is_empty() {
+                    bail!("No selected field is of type {}", type_filter.join(" or "));
+                }
+                fields_to_get
+            };
+
+            if values_only && fields_to_get.len() > 1 && separator.is_none() {
+                bail!("--values-only with several fields requires a --separator");
+            }
+
+            let avro_output = output_format.as_deref() == Some("avro");
+            let table_output = output_format.is_none() && !values_only && template.is_none();
+            if append && avro_output {
+                bail!("--append can't be used with the avro format");
+            }
+            if avro_output && watch {
+                bail!("--watch can't be used with the avro format");
+            }
+            let json_array_output =
+                matches!(output_format.as_deref(), Some("json" | "json-pretty"));
+            if json_array_output && (append || watch) {
+                bail!("--append and --watch can't be used with a JSON array, use the ndjson format to write one record per line");
+            }
+            if codec.is_some() && !avro_output {
+                bail!("--codec only applies to the avro format");
+            }
+            let codec = codec.unwrap_or_default().codec()?;
+            if avro_output
+                && (explode_map.is_some() || hash || with_size || with_schema_id || flatten)
+            {
+                bail!("--explode-map, --hash, --with-size, --with-schema-id and --flatten-all can't be used with the avro format");
+            }
+            if avro_output && annotate_unions {
+                bail!("--annotate-unions can't be used with the avro format, the values would no longer match the schema");
+            }
+            if avro_output && !coerce.is_empty() {
+                bail!("--coerce can't be used with the avro format, the values would no longer match the schema");
+            }
+            if output_encoding != OutputEncoding::Utf8 {
+                if avro_output || table_output {
+                    bail!("--output-encoding only applies to CSV, JSON, values-only and template output");
+                }
+                if bom {
+                    bail!("--bom only applies to UTF-8 output");
+                }
+            }
+            let encode = |out: Box<dyn Write>| -> Result<Box<dyn Write>> {
+                Ok(match output_encoding {
+                    OutputEncoding::Utf8 => out,
+                    encoding => Box::new(Transcoder::new(
+                        out,
+                        encoding,
+                        (!strict_encoding).then_some(encoding_substitute.as_str()),
+                    )?),
+                })
+            };
+            let projected_schema = if avro_output && output_dir.is_none() {
+                Some(schema::project(&avro.writer_schema()?, &fields_to_get)?)
+            } else {
+                None
+            };
+
+            // Shuffling needs every record, and takes from the shuffled rows instead
+            let mut rng = shuffle.then(|| match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            });
+            let options = GetOptions {
+                take: if shuffle { None } else { take },
+                fields_ci,
+                warn_over_bytes: (!yes).then_some(warn_over_bytes),
+                enum_validate,
+                fail_on_schema_warning,
+                annotate_unions,
+                raw_decimals: avro_output,
+                skip_errors,
+                abort_after_errors,
+                where_index,
+                explode_map,
+                explode_depth,
+                max_exploded_rows,
+                strict_utf8,
+                hash,
+                with_size,
+                with_schema_id,
+                union_branch: select_union_branch,
+                explain_errors,
+                time_range: time_field.map(|field| TimeRange {
+                    field,
+                    since,
+                    until,
+                }),
+                record_filter: record_filter_script
+                    .as_deref()
+                    .map(RecordFilter::parse)
+                    .transpose()?,
+                dedupe_by,
+                drop_empty,
+                replace_null,
+                coerce,
+            };
+            debug!("Getting fields {:?} with {:?}", fields_to_get, options);
+            let columns = options.columns(&fields_to_get);
+            let started = Instant::now();
+            let data = if output_dir.is_none() {
+                avro.get_fields(&fields_to_get, &options)?
+            } else {
+                // Read file by file below instead
+                Vec::new()
+            };
+            let (columns, mut data) = if flatten {
+                let (columns, data) = flatten_all(data, max_array_cols);
+                if columns.len() > FLATTEN_ALL_WARN_COLUMNS {
+                    eprintln!(
+                        "Warning: --flatten-all produced {} columns, consider picking fields",
+                        columns.len()
+                    );
+                }
+                (columns, data)
+            } else {
+                (columns, data)
+            };
+            sort_rows(&columns, &mut data, &sort_by)?;
+            if let Some(rng) = &mut rng {
+                data.shuffle(rng);
+                data.truncate(take.map_or(usize::MAX, |take| take as usize));
+            }
+
+            let format_options = FormatOptions {
+                max_depth,
+                decimal_as_float,
+                fixed_as_string,
+                group_digits: group_digits.then_some(locale),
+                normalize_timestamps,
+                longs_as_strings,
+                annotated_unions: annotate_unions,
+            };
+
+            let paged = !no_pager && !watch && (pager || std::io::stdout().is_terminal());
+            let mut remaining_rows = limit_output_rows;
+            let mut print = |mut data: AvroData,
+                             with_headers: bool,
+                             out: &mut dyn Write,
+                             projected_schema: Option<&Schema>|
+             -> Result<usize> {
+                let limit = remaining_rows.unwrap_or(usize::MAX);
+                if values_only || template.is_some() || output_format.is_some() {
+                    // Tables are limited once filtered by the search instead
+                    data.truncate(limit);
+                }
+
+                let printed = if let Some(template) = &template {
+                    print_as_template(out, template, data, &format_options)?
+                } else if values_only {
+                    print_values(
+                        out,
+                        data,
+                        separator.as_deref().unwrap_or_default(),
+                        &format_options,
+                    )?
+                } else {
+                    match output_format.as_deref() {
+                        None => print_as_table(
+                            output.is_some().then_some(out),
+                            &columns,
+                            data,
+                            &search,
+                            limit,
+                            &format_options,
+                            &TableOptions {
+                                title: title.as_deref(),
+                                header_color: header_color.into(),
+                                highlight_color: highlight_color.into(),
+                                na_color: na_color.into(),
+                                wrap: wrap.map(usize::from),
+                                align,
+                                paged,
+                            },
+                        )?,
+                        Some("csv") => {
+                            print_as_csv(out, &columns, data, with_headers, &format_options)
+                                .wrap_err("Could not print Avro as CSV")?
+                        }
+                        Some("tsv") => {
+                            print_as_tsv(out, &columns, data, with_headers, &format_options)
+                                .wrap_err("Could not print Avro as TSV")?
+                        }
+                        Some("json") => print_as_json(
+                            out,
+                            &columns,
+                            data,
+                            false,
+                            skip_nulls,
+                            json_arrays,
+                            &format_options,
+                        )
+                        .wrap_err("Could not print Avro as JSON")?,
+                        Some("ndjson") => print_as_ndjson(
+                            out,
+                            &columns,
+                            data,
+                            skip_nulls,
+                            json_arrays,
+                            &format_options,
+                        )
+                        .wrap_err("Could not print Avro as NDJSON")?,
+                        Some("json-pretty") => print_as_json(
+                            out,
+                            &columns,
+                            data,
+                            true,
+                            skip_nulls,
+                            json_arrays,
+                            &format_options,
+                        )
+                        .wrap_err("Could not print Avro as JSON")?,
+                        Some("json5") => {
+                            print_as_json5(out, &columns, data, skip_nulls, &format_options)
+                                .wrap_err("Could not print Avro as JSON5")?
+                        }
+                        Some("toml") => {
+                            print_as_toml(out, &columns, data, skip_nulls, &format_options)
+                                .wrap_err("Could not print Avro as TOML")?
+                        }
+                        Some("avro") => print_as_avro(
+                            out,
+                            projected_schema.expect("avro output has a schema"),
+                            codec,
+                            data,
+                        )
+                        .wrap_err("Could not write Avro")?,
+                        Some(_) => bail!("Output format not recognized"),
+                    }
+                };
+
+                if let Some(remaining) = &mut remaining_rows {
+                    *remaining -= printed;
+                }
+                Ok(printed)
+            };
+
+            let printed = match &output_dir {
+                None => {
+                    // Appending after earlier output, which has the header and BOM already
+                    let continued = append
+                        && output
+                            .as_ref()
+                            .and_then(|path| fs::metadata(path).ok())
+                            .is_some_and(|metadata| metadata.len() > 0);
+                    let mut out = encode(match &output {
+                        None => Box::new(stdout()),
+                        Some(path) => Box::new(BufWriter::new(
+                            OpenOptions::new()
+                                .write(true)
+                                .create(true)
+                                .append(append)
+                                .truncate(!append)
+                                .open(path)
+                                .into_diagnostic()
+                                .wrap_err_with(|| {
+                                    format!("Could not open file {}", path.display())
+                                })?,
+                        )),
+                    })?;
+                    if bom && !avro_output && !continued {
+                        out.write_all(UTF8_BOM).into_diagnostic()?;
+                    }
+                    let printed = print(data, !continued, &mut out, projected_schema.as_ref())?;
+                    out.flush().into_diagnostic()?;
+                    printed
+                }
+                Some(output_dir) => {
+                    fs::create_dir_all(output_dir)
+                        .into_diagnostic()
+                        .wrap_err_with(|| {
+                            format!("Could not create directory {}", output_dir.display())
+                        })?;
+                    let format = output_format
+                        .as_deref()
+                        .expect("--output-dir requires a format");
+                    let extension = output_ext
+                        .as_deref()
+                        .unwrap_or_else(|| default_extension(format));
+
+                    let mut written = HashSet::new();
+                    let mut printed = 0;
+                    for i in 0..avro.files().len() {
+                        let input = avro.files()[i].path();
+                        let stem = input.file_stem().unwrap_or(input.as_os_str());
+                        let output_path =
+                            output_dir.join(format!("{}.{}", stem.to_string_lossy(), extension));
+                        if !written.insert(output_path.clone()) {
+                            bail!(
+                                "Several input files would be written to {}",
+                                output_path.display()
+                            );
+                        }
+
+                        let projected_schema = if avro_output {
+                            let writer_schema =
+                                avro.files_mut()[i].reader()?.writer_schema().clone();
+                            Some(schema::project(&writer_schema, &fields_to_get)?)
+                        } else {
+                            None
+                        };
+                        let mut data = avro.get_file_fields(i, &fields_to_get, &options)?;
+                        sort_rows(&columns, &mut data, &sort_by)?;
+                        if let Some(rng) = &mut rng {
+                            data.shuffle(rng);
+                            data.truncate(take.map_or(usize::MAX, |take| take as usize));
+                        }
+                        let mut out =
+                            encode(Box::new(BufWriter::new(
+                                File::create(&output_path).into_diagnostic().wrap_err_with(
+                                    || format!("Could not create file {}", output_path.display()),
+                                )?,
+                            )))?;
+                        if bom && !avro_output {
+                            out.write_all(UTF8_BOM).into_diagnostic()?;
+                        }
+                        printed += print(data, true, &mut out, projected_schema.as_ref())?;
+                        out.flush().into_diagnostic()?;
+                    }
+                    printed
+                }
+            };
+            if report_timing {
+                report_throughput(&avro, started.elapsed());
+            }
+            if fail_on_empty && printed == 0 && !watch {
+                bail!("No records to output");
+            }
+
+            if watch {
+                loop {
+                    thread::sleep(Duration::from_millis(watch_interval));
+                    let data = avro.get_appended_fields(&fields_to_get, &options)?;
+                    if !data.is_empty() {
+                        print(data, false, &mut encode(Box::new(stdout()))?, None)?;
+                    }
+                }
+            }
+        }
+        RavroArgs::Split {
+            path,
+            buffer_capacity,
+            by,
+            output_dir,
+            max_open_writers,
+            codec,
+        } => {
+            let mut avro = CliService::from(path)?;
+            avro.set_buffer_capacity(buffer_capacity);
+            split::split(
+                &mut avro,
+                &by,
+                &output_dir,
+                max_open_writers,
+                codec.codec()?,
+            )?;
+        }
+        RavroArgs::Stats {
+            path,
+            buffer_capacity,
+            fields,
+            null_handling,
+            percentiles,
+        } => {
+            if let Some(p) = percentiles.iter().find(|p| !(0.0..=100.0).contains(*p)) {
+                bail!("Percentile {} is not between 0 and 100", p);
+            }
+            let mut avro = CliService::from(path)?;
+            avro.set_buffer_capacity(buffer_capacity);
+            let aggregates = stats::stats(
+                &mut avro,
+                &fields,
+                &GetOptions::default(),
+                null_handling,
+                !percentiles.is_empty(),
+            )?;
+            print_stats(aggregates, &percentiles);
+        }
+        RavroArgs::Audit {
             path,
-            search,
+            buffer_capacity,
+            fields,
             take,
-            output_format,
+            explain_union_stats,
         } => {
             let mut avro = CliService::from(path)?;
-            let fields_to_get = if fields_to_get.is_empty() {
-                avro.get_all_field_names()?
+            avro.set_buffer_capacity(buffer_capacity);
+            let options = GetOptions {
+                take,
+                ..GetOptions::default()
+            };
+            let presence = audit::audit(&mut avro, &fields, &options)?;
+            print_audit(&presence);
+            if explain_union_stats {
+                print_union_stats(&presence, &avro.writer_schema()?);
+            }
+        }
+        RavroArgs::Pivot {
+            path,
+            buffer_capacity,
+            key_field,
+            value_field,
+            index_field,
+        } => {
+            let mut avro = CliService::from(path)?;
+            avro.set_buffer_capacity(buffer_capacity);
+            let pivoted = pivot::pivot(
+                &mut avro,
+                &key_field,
+                &value_field,
+                index_field.as_deref(),
+                &GetOptions::default(),
+            )?;
+            print_pivot(pivoted);
+        }
+        RavroArgs::GroupBy {
+            path,
+            buffer_capacity,
+            by,
+            group_sort,
+        } => {
+            let mut avro = CliService::from(path)?;
+            avro.set_buffer_capacity(buffer_capacity);
+            let groups = group::group_by(&mut avro, &by, group_sort, &GetOptions::default())?;
+            print_groups(&by, groups);
+        }
+        RavroArgs::Repair {
+            path,
+            output,
+            codec,
+        } => {
+            let codec = codec.map(OutputCodec::codec).transpose()?;
+            let report = repair::repair(&path, &output, codec)?;
+            let mut stdout = stdout();
+            writeln!(
+                stdout,
+                "Kept {} records in {} blocks",
+                report.records_kept, report.blocks_kept
+            )
+            .into_diagnostic()?;
+            if report.blocks_dropped > 0 {
+                writeln!(
+                    stdout,
+                    "Dropped {} blocks with at least {} records, skipping {} bytes",
+                    report.blocks_dropped, report.records_dropped, report.bytes_skipped
+                )
+                .into_diagnostic()?;
+            }
+        }
+        RavroArgs::Repl {
+            path,
+            buffer_capacity,
+        } => {
+            let mut avro = CliService::from(path)?;
+            avro.set_buffer_capacity(buffer_capacity);
+            repl::run(avro)?;
+        }
+        RavroArgs::Compression { path } => {
+            let mut avro = CliService::from(path)?;
+            print_compression(compression::measure(&mut avro)?);
+        }
+        RavroArgs::Count {
+            path,
+            buffer_capacity,
+        } => {
+            let mut avro = CliService::from(path)?;
+            avro.set_buffer_capacity(buffer_capacity);
+            print_counts(avro.count_records()?);
+        }
+        RavroArgs::SchemaDrift { path } => {
+            let mut avro = CliService::from(path)?;
+            let clusters = avro.schema_clusters()?;
+            let mut stdout = stdout();
+            if let [(fingerprint, paths)] = &clusters[..] {
+                writeln!(
+                    stdout,
+                    "All {} files have the same schema, with fingerprint {}",
+                    paths.len(),
+                    fingerprint
+                )
+                .into_diagnostic()?;
             } else {
-                fields_to_get
+                for (fingerprint, paths) in &clusters {
+                    let files = if paths.len() == 1 { "file" } else { "files" };
+                    writeln!(stdout, "{} ({} {})", fingerprint, paths.len(), files)
+                        .into_diagnostic()?;
+                    for path in paths {
+                        writeln!(stdout, "  {}", path.display()).into_diagnostic()?;
+                    }
+                }
+                bail!(
+                    "Found {} different schemas across {} files",
+                    clusters.len(),
+                    avro.files().len()
+                );
+            }
+        }
+        RavroArgs::Schema {
+            path,
+            output_format,
+        } => {
+            let mut avro = CliService::from(path)?;
+            let schema = avro.writer_schema()?;
+            let text = match output_format.as_deref() {
+                None | Some("json") => serde_json::to_string(&schema).into_diagnostic()?,
+                Some("json-pretty") => serde_json::to_string_pretty(&schema).into_diagnostic()?,
+                Some("canonical") => schema.canonical_form(),
+                Some(_) => bail!("Output format not recognized"),
             };
+            writeln!(stdout(), "{}", text).into_diagnostic()?;
+        }
+        RavroArgs::JsonSchema { path } => {
+            let mut avro = CliService::from(path)?;
+            let json_schema = json_schema::from_avro(&avro.writer_schema()?);
+            let mut stdout = stdout();
+            serde_json::to_writer_pretty(&mut stdout, &json_schema).into_diagnostic()?;
+            writeln!(&mut stdout).into_diagnostic()?;
+        }
+    }
 
-            let data = avro.get_fields(&fields_to_get, take)?;
+    Ok(())
+}
 
-            match output_format {
-                None => print_as_table(&fields_to_get, data, search)?,
-                Some(format_option) => match format_option.as_ref() {
-                    "csv" => print_as_csv(&fields_to_get, data)
-                        .wrap_err("Could not print Avro as CSV")?,
-                    "json" => print_as_json(&fields_to_get, data, false)
-                        .wrap_err("Could not print Avro as JSON")?,
-                    "json-pretty" => print_as_json(&fields_to_get, data, true)
-                        .wrap_err("Could not print Avro as JSON")?,
-                    _ => bail!("Output format not recognized"),
-                },
-            }
+/// Log to stderr at a level set by how many times `-v` is given. Without it, nothing is logged
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => return,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .init();
+}
+
+/// Standard output that exits quietly once the reader goes away, e.g. `head`, like other
+/// Unix tools do on `SIGPIPE`
+struct Stdout(std::io::Stdout);
+
+fn stdout() -> Stdout {
+    Stdout(std::io::stdout())
+}
+
+impl Stdout {
+    fn exit_on_broken_pipe<T>(result: std::io::Result<T>) -> std::io::Result<T> {
+        match result {
+            Err(err) if err.kind() == ErrorKind::BrokenPipe => std::process::exit(0),
+            result => result,
         }
     }
+}
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Self::exit_on_broken_pipe(self.0.write(buf))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Self::exit_on_broken_pipe(self.0.flush())
+    }
+}
+
+/// Extension of files written in a format
+fn default_extension(format: &str) -> &str {
+    match format {
+        "json-pretty" => "json",
+        format => format,
+    }
+}
 
+fn print_glob_explanation(pattern: &str, follow_symlinks: bool) -> Result<()> {
+    let matches = expand_glob(pattern, follow_symlinks)?;
+    eprintln!("Pattern `{}` matched:", pattern);
+    for matched in &matches {
+        match &matched.skipped {
+            None => eprintln!("  {} ({})", matched.path.display(), matched.kind),
+            Some(reason) => eprintln!(
+                "  {} ({}), left out: {}",
+                matched.path.display(),
+                matched.kind,
+                reason
+            ),
+        }
+    }
+    let read = matches.iter().filter(|m| m.skipped.is_none()).count();
+    eprintln!("{} paths matched, {} to read", matches.len(), read);
     Ok(())
 }
 
-fn print_as_table(field_names: &[String], data: AvroData, search: Option<String>) -> Result<()> {
+/// Read a schema from a file, or from stdin for `-`
+fn read_schema(path: &Path) -> Result<Schema> {
+    let schema = if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin()).into_diagnostic()
+    } else {
+        fs::read_to_string(path).into_diagnostic()
+    };
+    schema
+        .and_then(|schema| Schema::parse_str(&schema).into_diagnostic())
+        .wrap_err_with(|| format!("Could not read schema {}", path.display()))
+}
+
+fn report_throughput(avro: &CliService, elapsed: Duration) {
+    let records = avro.records_read();
+    eprintln!(
+        "Read {} records ({} bytes) in {:.3}s: {:.0} records/s",
+        records,
+        avro.bytes_read(),
+        elapsed.as_secs_f64(),
+        records as f64 / elapsed.as_secs_f64()
+    );
+}
+
+fn print_stats(aggregates: Vec<(String, Aggregate)>, percentiles: &[f64]) {
     let mut table = Table::new();
+    let percentile_headers: Vec<String> = percentiles.iter().map(|p| format!("p{}", p)).collect();
+    table.add_row(Row::new(
+        ["field", "count", "skipped", "sum", "mean", "min", "max"]
+            .into_iter()
+            .chain(percentile_headers.iter().map(String::as_str))
+            .map(|header| {
+                Cell::new(header)
+                    .with_style(Attr::Bold)
+                    .with_style(Attr::ForegroundColor(color::BLUE))
+                    .with_style(Attr::Underline(true))
+            })
+            .collect(),
+    ));
 
-    let search = match search {
-        None => None,
-        Some(re) => Some(Regex::new(&re).into_diagnostic()?),
-    };
+    let optional = |number: Option<f64>| number.map_or(NA.to_owned(), |n| n.to_string());
+    for (field, mut aggregate) in aggregates {
+        let mut row = vec![
+            Cell::new(&field).with_style(Attr::Bold),
+            Cell::new(&aggregate.count.to_string()),
+            Cell::new(&aggregate.skipped.to_string()),
+            Cell::new(&aggregate.sum.to_string()),
+            Cell::new(&optional(aggregate.mean())),
+            Cell::new(&optional(aggregate.min)),
+            Cell::new(&optional(aggregate.max)),
+        ];
+        for value in aggregate.percentiles(percentiles) {
+            row.push(Cell::new(&optional(value)));
+        }
+        table.add_row(Row::new(row));
+    }
 
-    let header_cells: Vec<Cell> = field_names
+    table.printstd();
+}
+
+fn print_audit(presence: &[(String, Presence)]) {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["field", "records", "N/A", "null"]
+            .iter()
+            .map(|header| {
+                Cell::new(header)
+                    .with_style(Attr::Bold)
+                    .with_style(Attr::ForegroundColor(color::BLUE))
+                    .with_style(Attr::Underline(true))
+            })
+            .collect(),
+    ));
+
+    let percent =
+        |fraction: Option<f64>| fraction.map_or(NA.to_owned(), |f| format!("{:.1}%", f * 100.0));
+    for (field, presence) in presence {
+        let mut field_cell = Cell::new(field).with_style(Attr::Bold);
+        if presence.always_empty() {
+            field_cell = field_cell.with_style(Attr::ForegroundColor(color::RED));
+        }
+        table.add_row(Row::new(vec![
+            field_cell,
+            Cell::new(&presence.records.to_string()),
+            Cell::new(&percent(presence.missing_fraction())),
+            Cell::new(&percent(presence.null_fraction())),
+        ]));
+    }
+
+    table.printstd();
+}
+
+fn print_counts(counts: Vec<(PathBuf, u64)>) {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["file", "records"]
+            .iter()
+            .map(|header| {
+                Cell::new(header)
+                    .with_style(Attr::Bold)
+                    .with_style(Attr::ForegroundColor(color::BLUE))
+                    .with_style(Attr::Underline(true))
+            })
+            .collect(),
+    ));
+
+    let total: u64 = counts.iter().map(|(_, count)| count).sum();
+    for (path, count) in counts {
+        table.add_row(Row::new(vec![
+            Cell::new(&path.display().to_string()).with_style(Attr::Bold),
+            Cell::new(&count.to_string()),
+        ]));
+    }
+    table.add_row(Row::new(vec![
+        Cell::new("total").with_style(Attr::Bold),
+        Cell::new(&total.to_string()).with_style(Attr::Bold),
+    ]));
+
+    table.printstd();
+}
+
+/// Branch names come from the schema of the first file
+fn print_union_stats(presence: &[(String, Presence)], schema: &Schema) {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["field", "branch", "records", "share"]
+            .iter()
+            .map(|header| {
+                Cell::new(header)
+                    .with_style(Attr::Bold)
+                    .with_style(Attr::ForegroundColor(color::BLUE))
+                    .with_style(Attr::Underline(true))
+            })
+            .collect(),
+    ));
+
+    for (field, presence) in presence.iter().filter(|(_, presence)| presence.records > 0) {
+        let names = audit::union_branch_names(schema, field);
+        // Branches no record holds are listed too
+        for index in 0..names.len().max(presence.branches.len()) {
+            let count = presence.branches.get(index).copied().unwrap_or(0);
+            let name = names
+                .get(index)
+                .cloned()
+                .unwrap_or_else(|| format!("branch {}", index));
+            table.add_row(Row::new(vec![
+                Cell::new(field).with_style(Attr::Bold),
+                Cell::new(&name),
+                Cell::new(&count.to_string()),
+                Cell::new(&format!(
+                    "{:.1}%",
+                    count as f64 / presence.records as f64 * 100.0
+                )),
+            ]));
+        }
+    }
+
+    table.printstd();
+}
+
+fn print_compression(reports: Vec<compression::CompressionReport>) {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        [
+            "file",
+            "codec",
+            "blocks",
+            "records",
+            "file size",
+            "compressed",
+            "uncompressed",
+            "ratio",
+        ]
         .iter()
-        .map(|f| {
-            Cell::new(f)
+        .map(|header| {
+            Cell::new(header)
                 .with_style(Attr::Bold)
                 .with_style(Attr::ForegroundColor(color::BLUE))
                 .with_style(Attr::Underline(true))
         })
-        .collect();
-    table.add_row(Row::new(header_cells));
+        .collect(),
+    ));
+
+    for report in reports {
+        table.add_row(Row::new(vec![
+            Cell::new(&report.path.display().to_string()).with_style(Attr::Bold),
+            Cell::new(compression::codec_name(report.codec)),
+            Cell::new(&report.blocks.to_string()),
+            Cell::new(&report.records.to_string()),
+            Cell::new(&report.file_size.to_string()),
+            Cell::new(&report.compressed.to_string()),
+            Cell::new(&report.uncompressed.to_string()),
+            Cell::new(
+                &report
+                    .ratio()
+                    .map_or(NA.to_owned(), |ratio| format!("{:.2}", ratio)),
+            ),
+        ]));
+    }
+
+    table.printstd();
+}
+
+fn print_pivot(pivoted: pivot::Pivoted) {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        pivoted
+            .columns
+            .iter()
+            .map(|header| {
+                Cell::new(header)
+                    .with_style(Attr::Bold)
+                    .with_style(Attr::ForegroundColor(color::BLUE))
+                    .with_style(Attr::Underline(true))
+            })
+            .collect(),
+    ));
+
+    for row in pivoted.rows {
+        table.add_row(Row::new(
+            row.iter()
+                .map(|value| Cell::new(&value.to_string()))
+                .collect(),
+        ));
+    }
+
+    table.printstd();
+}
+
+fn print_groups(by: &[String], groups: AvroData) {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        by.iter()
+            .map(String::as_str)
+            .chain([group::COUNT_COLUMN])
+            .map(|header| {
+                Cell::new(header)
+                    .with_style(Attr::Bold)
+                    .with_style(Attr::ForegroundColor(color::BLUE))
+                    .with_style(Attr::Underline(true))
+            })
+            .collect(),
+    ));
+
+    for row in groups {
+        table.add_row(Row::new(
+            row.iter()
+                .map(|value| Cell::new(&value.value().to_string()))
+                .collect(),
+        ));
+    }
+
+    table.printstd();
+}
+
+/// Colours a table can be printed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum TableColor {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+}
+
+impl From<TableColor> for color::Color {
+    fn from(table_color: TableColor) -> Self {
+        match table_color {
+            TableColor::Black => color::BLACK,
+            TableColor::Red => color::RED,
+            TableColor::Green => color::GREEN,
+            TableColor::Yellow => color::YELLOW,
+            TableColor::Blue => color::BLUE,
+            TableColor::Magenta => color::MAGENTA,
+            TableColor::Cyan => color::CYAN,
+            TableColor::White => color::WHITE,
+            TableColor::BrightBlack => color::BRIGHT_BLACK,
+            TableColor::BrightRed => color::BRIGHT_RED,
+            TableColor::BrightGreen => color::BRIGHT_GREEN,
+            TableColor::BrightYellow => color::BRIGHT_YELLOW,
+            TableColor::BrightBlue => color::BRIGHT_BLUE,
+            TableColor::BrightMagenta => color::BRIGHT_MAGENTA,
+            TableColor::BrightCyan => color::BRIGHT_CYAN,
+            TableColor::BrightWhite => color::BRIGHT_WHITE,
+        }
+    }
+}
+
+/// How table columns are aligned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum TableAlign {
+    Left,
+    Right,
+    /// Right-align columns of numbers, left-align the others
+    Auto,
+}
+
+/// How to decorate and show a table
+struct TableOptions<'a> {
+    title: Option<&'a str>,
+    header_color: color::Color,
+    highlight_color: color::Color,
+    na_color: color::Color,
+    /// Wrap cells to at most this many characters wide
+    wrap: Option<usize>,
+    align: TableAlign,
+    /// Show the table through a pager
+    paged: bool,
+}
+
+/// Print records as a table, returning how many were printed. The table goes to the
+/// terminal when `out` is None, through the pager when enabled. Tables written to `out`
+/// are plain text, without colours.
+fn print_as_table(
+    out: Option<&mut dyn Write>,
+    field_names: &[String],
+    data: AvroData,
+    search: &[String],
+    limit: usize,
+    format_options: &FormatOptions,
+    table_options: &TableOptions,
+) -> Result<usize> {
+    let mut table = Table::new();
+
+    if let Some(title) = table_options.title {
+        let mut title_cell = Cell::new(title)
+            .with_style(Attr::Bold)
+            .with_hspan(field_names.len().max(1));
+        title_cell.align(Alignment::CENTER);
+        table.add_row(Row::new(vec![title_cell]));
+    }
+
+    let search = search
+        .iter()
+        .map(|re| Regex::new(re).into_diagnostic())
+        .collect::<Result<Vec<Regex>>>()?;
+    if !search.is_empty() {
+        debug!("Compiled search patterns {:?}", search);
+    }
+    let is_match = |value_str: &str| search.iter().any(|re| re.is_match(value_str));
 
-    let filtered_data: AvroData = data
+    let rendered_data = data
         .into_iter()
-        .filter(|r| {
-            r.iter()
-                .find(|v| match &search {
-                    None => true,
-                    Some(search) => search.is_match(&v.value().to_string()),
-                })
-                .is_some()
+        .map(|r| {
+            r.into_iter()
+                .map(|v| v.value().format(format_options).map(|s| (v, s)))
+                .collect::<Result<Vec<(AvroColumnarValue, String)>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let filtered_data: Vec<Vec<(AvroColumnarValue, String)>> = rendered_data
+        .into_iter()
+        .filter(|r| search.is_empty() || r.iter().any(|(_, value_str)| is_match(value_str)))
+        .take(limit)
+        .collect();
+
+    let alignments: Vec<Alignment> = (0..field_names.len())
+        .map(|column| {
+            let right = match table_options.align {
+                TableAlign::Left => false,
+                TableAlign::Right => true,
+                TableAlign::Auto => is_numeric_column(
+                    filtered_data
+                        .iter()
+                        .filter_map(|row| row.get(column))
+                        .map(|(v, _)| v.value()),
+                ),
+            };
+            if right {
+                Alignment::RIGHT
+            } else {
+                Alignment::LEFT
+            }
+        })
+        .collect();
+
+    let header_cells: Vec<Cell> = field_names
+        .iter()
+        .zip(&alignments)
+        .map(|(f, &alignment)| {
+            let mut cell = Cell::new(f)
+                .with_style(Attr::Bold)
+                .with_style(Attr::ForegroundColor(table_options.header_color))
+                .with_style(Attr::Underline(true));
+            cell.align(alignment);
+            cell
         })
         .collect();
+    table.add_row(Row::new(header_cells));
 
+    let printed = filtered_data.len();
     for fields_for_row in filtered_data {
         let row_cells: Vec<Cell> = fields_for_row
             .iter()
-            .filter_map(|v: &AvroColumnarValue| {
-                let value_str = v.value().to_string();
-                let mut cell = Cell::new(&value_str);
-                if let Some(search) = &search {
-                    if search.is_match(&value_str) {
-                        cell.style(Attr::Bold);
-                        cell.style(Attr::ForegroundColor(color::GREEN));
-                    }
+            .zip(&alignments)
+            .filter_map(|((v, value_str), &alignment)| {
+                let mut cell = match table_options.wrap {
+                    Some(width) => Cell::new(&wrap_text(value_str, width)),
+                    None => Cell::new(value_str),
+                };
+                cell.align(alignment);
+                if is_match(value_str) {
+                    cell.style(Attr::Bold);
+                    cell.style(Attr::ForegroundColor(table_options.highlight_color));
                 }
 
                 match v.value() {
-                    AvroValue::Na => cell.style(Attr::ForegroundColor(color::RED)),
+                    AvroValue::Na => cell.style(Attr::ForegroundColor(table_options.na_color)),
                     _ => {}
                 }
 
@@ -130,48 +1922,473 @@ fn print_as_table(field_names: &[String], data: AvroData, search: Option<String>
         table.add_row(Row::new(row_cells));
     }
 
-    table.printstd();
-    Ok(())
+    match out {
+        Some(out) => {
+            table.print(out).into_diagnostic()?;
+        }
+        None => pager::print_table(&table, table_options.paged)?,
+    }
+    Ok(printed)
+}
+
+/// Whether a column has numbers, and nothing but numbers besides nulls and N/A
+fn is_numeric_column<'a>(values: impl Iterator<Item = &'a AvroValue>) -> bool {
+    fn is_numeric(value: &Value) -> Option<bool> {
+        match value {
+            Value::Null => None,
+            Value::Union(_, value) => is_numeric(value),
+            Value::Int(_)
+            | Value::Long(_)
+            | Value::Float(_)
+            | Value::Double(_)
+            | Value::Decimal(_)
+            | Value::BigDecimal(_) => Some(true),
+            _ => Some(false),
+        }
+    }
+
+    let mut numbers = 0;
+    for value in values {
+        if let AvroValue::Value(value) = value {
+            match is_numeric(value) {
+                Some(true) => numbers += 1,
+                Some(false) => return false,
+                None => {}
+            }
+        }
+    }
+    numbers > 0
+}
+
+/// Break text into lines of at most `width` characters at whitespace, splitting words that
+/// are longer than that. Line breaks already in the text are kept
+fn wrap_text(text: &str, width: usize) -> String {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        let mut line_width = 0;
+        for word in paragraph.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            if line_width > 0 && line_width + 1 + word.len() > width {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+            }
+            while word.len() > width {
+                lines.push(word.drain(..width).collect());
+            }
+            if word.is_empty() {
+                continue;
+            }
+            if line_width > 0 {
+                line.push(' ');
+                line_width += 1;
+            }
+            line_width += word.len();
+            line.extend(word);
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+fn print_values<W: Write>(
+    mut out: W,
+    data: AvroData,
+    separator: &str,
+    format_options: &FormatOptions,
+) -> Result<usize> {
+    let printed = data.len();
+    for row in data {
+        let values = row
+            .iter()
+            .map(|val| val.value().format(format_options))
+            .collect::<Result<Vec<String>>>()?;
+        writeln!(out, "{}", values.join(separator)).into_diagnostic()?;
+    }
+    Ok(printed)
+}
+
+fn print_as_template<W: Write>(
+    mut out: W,
+    template: &template::Template,
+    data: AvroData,
+    format_options: &FormatOptions,
+) -> Result<usize> {
+    let printed = data.len();
+    for row in data {
+        writeln!(out, "{}", template.render(&row, format_options)?).into_diagnostic()?;
+    }
+    Ok(printed)
+}
+
+fn print_as_csv<W: Write>(
+    out: W,
+    field_names: &[String],
+    data: AvroData,
+    with_headers: bool,
+    format_options: &FormatOptions,
+) -> Result<usize> {
+    print_delimited(
+        csv::Writer::from_writer(out),
+        field_names,
+        data,
+        with_headers,
+        format_options,
+    )
 }
 
-fn print_as_csv(field_names: &[String], data: AvroData) -> Result<()> {
-    let mut writer = csv::Writer::from_writer(std::io::stdout());
+/// Like CSV, but with tabs between values, as spreadsheets paste them
+fn print_as_tsv<W: Write>(
+    out: W,
+    field_names: &[String],
+    data: AvroData,
+    with_headers: bool,
+    format_options: &FormatOptions,
+) -> Result<usize> {
+    print_delimited(
+        csv::WriterBuilder::new().delimiter(b'\t').from_writer(out),
+        field_names,
+        data,
+        with_headers,
+        format_options,
+    )
+}
+
+/// Write records with a CSV writer, which sets the delimiter and quoting
+fn print_delimited<W: Write>(
+    mut writer: csv::Writer<W>,
+    field_names: &[String],
+    data: AvroData,
+    with_headers: bool,
+    format_options: &FormatOptions,
+) -> Result<usize> {
+    let printed = data.len();
 
-    // Headers
-    writer.write_record(field_names).into_diagnostic()?;
+    if with_headers {
+        writer.write_record(field_names).into_diagnostic()?;
+    }
 
     for row in data {
         writer
             .write_record(
                 row.iter()
-                    .map(|val: &AvroColumnarValue| val.value().to_string())
-                    .collect::<Vec<String>>(),
+                    .map(|val: &AvroColumnarValue| val.value().format(format_options))
+                    .collect::<Result<Vec<String>>>()?,
             )
             .into_diagnostic()?;
     }
 
     writer.flush().into_diagnostic()?;
-    Ok(())
+    Ok(printed)
+}
+
+/// Write records as an Avro file with the projected schema
+fn print_as_avro<W: Write>(out: W, schema: &Schema, codec: Codec, data: AvroData) -> Result<usize> {
+    let mut writer = Writer::with_codec(schema, out, codec);
+
+    let printed = data.len();
+    for (i, row) in data.into_iter().enumerate() {
+        let fields = row
+            .into_iter()
+            .map(|val| match val.value() {
+                AvroValue::Value(value) => Ok((val.name().to_owned(), value.clone())),
+                AvroValue::Na => bail!("Record {} has no field `{}`", i, val.name()),
+            })
+            .collect::<Result<Vec<(String, Value)>>>()?;
+        writer.append(Value::Record(fields)).into_diagnostic()?;
+    }
+
+    writer
+        .into_inner()
+        .into_diagnostic()?
+        .flush()
+        .into_diagnostic()?;
+    Ok(printed)
+}
+
+fn print_as_json5<W: Write>(
+    mut out: W,
+    field_filter: &[String],
+    data: AvroData,
+    skip_nulls: bool,
+    format_options: &FormatOptions,
+) -> Result<usize> {
+    let printed = data.len();
+    for row in data {
+        let fields = row
+            .iter()
+            .filter(|val| field_filter.iter().any(|f| val.name() == f))
+            .map(|val: &AvroColumnarValue| {
+                val.value()
+                    .to_json5(format_options)
+                    .map(|v| (val.name().to_owned(), v))
+            })
+            .filter(|field| !(skip_nulls && matches!(field, Ok((_, v)) if v == NULL)))
+            .collect::<Result<Vec<(String, String)>>>()?;
+        writeln!(out, "{}", json5_object(fields)).into_diagnostic()?;
+    }
+    Ok(printed)
+}
+
+/// Print a single record as a TOML table, or several as an array of `[[record]]` tables.
+/// TOML has no null, so null values are errors unless `skip_nulls` leaves them out
+fn print_as_toml<W: Write>(
+    mut out: W,
+    field_filter: &[String],
+    data: AvroData,
+    skip_nulls: bool,
+    format_options: &FormatOptions,
+) -> Result<usize> {
+    let printed = data.len();
+    let mut records = data
+        .into_iter()
+        .map(|row| {
+            row.iter()
+                .filter(|val| field_filter.iter().any(|f| val.name() == f))
+                .map(|val| -> Result<(String, serde_json::Value)> {
+                    Ok((val.name().to_owned(), val.value().to_json(format_options)?))
+                })
+                .filter(|field| !(skip_nulls && matches!(field, Ok((_, serde_json::Value::Null)))))
+                .map(|field| {
+                    let (name, value) = field?;
+                    let value = json_to_toml(value, &name)?;
+                    Ok((name, value))
+                })
+                .collect::<Result<toml::Table>>()
+        })
+        .collect::<Result<Vec<toml::Table>>>()?;
+
+    let document = if records.len() == 1 {
+        records.remove(0)
+    } else {
+        toml::Table::from_iter([(
+            "record".to_owned(),
+            toml::Value::Array(records.into_iter().map(toml::Value::Table).collect()),
+        )])
+    };
+    let document = toml::to_string(&document).into_diagnostic()?;
+    out.write_all(document.as_bytes()).into_diagnostic()?;
+    Ok(printed)
+}
+
+fn json_to_toml(value: serde_json::Value, path: &str) -> Result<toml::Value> {
+    Ok(match value {
+        serde_json::Value::Null => bail!(
+            "`{}` is null, which TOML can't represent. Leave null fields out with --skip-nulls",
+            path
+        ),
+        serde_json::Value::Bool(b) => toml::Value::Boolean(b),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => toml::Value::Integer(i),
+            (None, Some(f)) => toml::Value::Float(f),
+            (None, None) => bail!("`{}` is {}, which TOML can't represent", path, n),
+        },
+        serde_json::Value::String(s) => toml::Value::String(s),
+        serde_json::Value::Array(items) => toml::Value::Array(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| json_to_toml(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_>>()?,
+        ),
+        serde_json::Value::Object(map) => toml::Value::Table(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let value = json_to_toml(value, &format!("{}.{}", path, key))?;
+                    Ok((key, value))
+                })
+                .collect::<Result<_>>()?,
+        ),
+    })
+}
+
+/// Write all records as a single JSON array
+fn print_as_json<W: Write>(
+    mut out: W,
+    field_filter: &[String],
+    data: AvroData,
+    pretty: bool,
+    skip_nulls: bool,
+    arrays: bool,
+    format_options: &FormatOptions,
+) -> Result<usize> {
+    let printed = data.len();
+    let rows = data
+        .iter()
+        .map(|row| json_row(row, field_filter, skip_nulls, arrays, format_options))
+        .collect::<Result<Vec<serde_json::Value>>>()?;
+    if pretty {
+        serde_json::to_writer_pretty(&mut out, &rows).into_diagnostic()?;
+    } else {
+        serde_json::to_writer(&mut out, &rows).into_diagnostic()?;
+    }
+    writeln!(out).into_diagnostic()?;
+    Ok(printed)
 }
 
-fn print_as_json(field_filter: &[String], data: AvroData, pretty: bool) -> Result<()> {
-    let mut stdout = std::io::stdout();
+/// Write each record as a JSON value on its own line
+fn print_as_ndjson<W: Write>(
+    mut out: W,
+    field_filter: &[String],
+    data: AvroData,
+    skip_nulls: bool,
+    arrays: bool,
+    format_options: &FormatOptions,
+) -> Result<usize> {
+    let printed = data.len();
     for row in data {
-        let obj = serde_json::Value::Object(
+        let obj = json_row(&row, field_filter, skip_nulls, arrays, format_options)?;
+        serde_json::to_writer(&mut out, &obj).into_diagnostic()?;
+        writeln!(out).into_diagnostic()?;
+    }
+    Ok(printed)
+}
+
+/// A record as a JSON object, or an array of its values in field order with `arrays`
+fn json_row(
+    row: &[AvroColumnarValue],
+    field_filter: &[String],
+    skip_nulls: bool,
+    arrays: bool,
+    format_options: &FormatOptions,
+) -> Result<serde_json::Value> {
+    Ok(if arrays {
+        serde_json::Value::Array(
+            field_filter
+                .iter()
+                .map(|f| match row.iter().find(|val| val.name() == f) {
+                    Some(val) => val.value().to_json(format_options),
+                    None => Ok(serde_json::Value::Null),
+                })
+                .collect::<Result<Vec<serde_json::Value>>>()?,
+        )
+    } else {
+        serde_json::Value::Object(
             row.iter()
                 .filter(|val| field_filter.iter().any(|f| val.name() == f))
                 .map(|val: &AvroColumnarValue| {
-                    val.value().to_json().map(|v| (val.name().to_owned(), v))
+                    val.value()
+                        .to_json(format_options)
+                        .map(|v| (val.name().to_owned(), v))
                 })
+                .filter(|field| !(skip_nulls && matches!(field, Ok((_, serde_json::Value::Null)))))
                 .collect::<Result<serde_json::Map<String, serde_json::Value>>>()?,
-        );
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        if pretty {
-            serde_json::to_writer_pretty(&mut stdout, &obj).into_diagnostic()?;
-        } else {
-            serde_json::to_writer(&mut stdout, &obj).into_diagnostic()?;
+    #[test]
+    fn test_wrap_text() {
+        let cases = [
+            ("", 5, ""),
+            ("short", 10, "short"),
+            ("exact", 5, "exact"),
+            ("the quick brown fox", 10, "the quick\nbrown fox"),
+            ("  spaced   out  ", 10, "spaced out"),
+            ("abcdefghijkl", 5, "abcde\nfghij\nkl"),
+            ("abcdefgh", 4, "abcd\nefgh"),
+            ("ab abcdefghijk", 4, "ab\nabcd\nefgh\nijk"),
+            ("ééééé", 3, "ééé\néé"),
+            ("one\ntwo three", 7, "one\ntwo\nthree"),
+            ("a\n\nb", 10, "a\n\nb"),
+        ];
+        for (text, width, wrapped) in cases {
+            assert_eq!(
+                wrap_text(text, width),
+                wrapped,
+                "{:?} at width {}",
+                text,
+                width
+            );
         }
-        writeln!(&mut stdout, "").into_diagnostic()?;
     }
-    Ok(())
+
+    #[test]
+    fn test_follow_symlinks_overrides() {
+        let no_follow = |args: &[&str]| {
+            let args =
+                Args::try_parse_from(["explore-avro", "get", "x.avro"].iter().chain(args)).unwrap();
+            match args.command {
+                RavroArgs::Get {
+                    no_follow_symlinks, ..
+                } => no_follow_symlinks,
+                _ => unreachable!(),
+            }
+        };
+        assert!(!no_follow(&[]));
+        assert!(no_follow(&["--no-follow-symlinks"]));
+        assert!(!no_follow(&["--no-follow-symlinks", "--follow-symlinks"]));
+        assert!(no_follow(&["--follow-symlinks", "--no-follow-symlinks"]));
+    }
+
+    #[test]
+    fn test_print_as_tsv() {
+        let columns = vec!["name".to_owned(), "note".to_owned()];
+        let data = vec![vec![
+            AvroColumnarValue::from(
+                "name".to_owned(),
+                AvroValue::from(Value::String("Alice".to_owned())),
+            ),
+            AvroColumnarValue::from(
+                "note".to_owned(),
+                AvroValue::from(Value::String("a\tb".to_owned())),
+            ),
+        ]];
+
+        let mut out = Vec::new();
+        let printed =
+            print_as_tsv(&mut out, &columns, data, true, &FormatOptions::default()).unwrap();
+        assert_eq!(printed, 1);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "name\tnote\nAlice\t\"a\tb\"\n"
+        );
+    }
+
+    #[test]
+    fn test_print_as_table_to_output() {
+        let columns = vec!["name".to_owned(), "age".to_owned()];
+        let data = vec![vec![
+            AvroColumnarValue::from(
+                "name".to_owned(),
+                AvroValue::from(Value::String("Alice".to_owned())),
+            ),
+            AvroColumnarValue::from("age".to_owned(), AvroValue::na()),
+        ]];
+        let table_options = TableOptions {
+            title: None,
+            header_color: color::BLUE,
+            highlight_color: color::GREEN,
+            na_color: color::RED,
+            wrap: None,
+            align: TableAlign::Left,
+            paged: false,
+        };
+
+        let mut out = Vec::new();
+        let printed = print_as_table(
+            Some(&mut out),
+            &columns,
+            data,
+            &["Alice".to_owned()],
+            usize::MAX,
+            &FormatOptions::default(),
+            &table_options,
+        )
+        .unwrap();
+        assert_eq!(printed, 1);
+        // Neither the header, the match nor N/A are coloured in a file
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "+-------+-----+\n\
+             | name  | age |\n\
+             +-------+-----+\n\
+             | Alice | N/A |\n\
+             +-------+-----+\n"
+        );
+    }
 }
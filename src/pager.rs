@@ -0,0 +1,71 @@
+use miette::{IntoDiagnostic, Result};
+use prettytable::Table;
+use std::env;
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+use std::sync::Once;
+use term::terminfo::{TermInfo, TerminfoTerminal};
+
+const DEFAULT_PAGER: &str = "less";
+
+/// Get the pager command from `RAVRO_PAGER`, then `PAGER`, defaulting to `less`.
+/// An empty value or `cat` means no pager.
+fn pager_command() -> Option<String> {
+    let command = env::var("RAVRO_PAGER")
+        .or_else(|_| env::var("PAGER"))
+        .unwrap_or_else(|_| DEFAULT_PAGER.to_owned());
+    let command = command.trim();
+    (!command.is_empty() && command != "cat").then(|| command.to_owned())
+}
+
+/// Print a table to stdout, through a pager when `paged` is true and one is configured.
+/// Colours are kept when the terminal supports them. When the pager can't be started, e.g.
+/// `less` isn't installed, the table is printed directly, with a warning the first time.
+pub(crate) fn print_table(table: &Table, paged: bool) -> Result<()> {
+    let Some(command) = pager_command().filter(|_| paged) else {
+        table.printstd();
+        return Ok(());
+    };
+
+    let mut args = command.split_whitespace();
+    let program = args.next().unwrap_or(DEFAULT_PAGER);
+    let mut pager = Command::new(program);
+    pager.args(args).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        // Like git: quit if it fits on one screen, keep colours, don't clear the screen
+        pager.env("LESS", "FRX");
+    }
+    let mut child = match pager.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            static WARNED: Once = Once::new();
+            WARNED.call_once(|| {
+                eprintln!(
+                    "Warning: could not start pager `{}`, printing directly: {} (set RAVRO_PAGER or use --no-pager)",
+                    command, err
+                );
+            });
+            table.printstd();
+            return Ok(());
+        }
+    };
+
+    let stdin = child.stdin.take().expect("pager stdin is piped");
+    let printed = match TermInfo::from_env() {
+        Ok(terminfo) => table
+            .print_term(&mut TerminfoTerminal::new_with_terminfo(stdin, terminfo))
+            .map(drop),
+        Err(_) => {
+            let mut stdin = stdin;
+            table.print(&mut stdin).and_then(|_| stdin.flush())
+        }
+    };
+    match printed {
+        // The pager was closed before reading everything
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => {}
+        other => other.into_diagnostic()?,
+    }
+
+    child.wait().into_diagnostic()?;
+    Ok(())
+}
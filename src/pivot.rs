@@ -0,0 +1,72 @@
+use crate::avro_value::AvroValue;
+use crate::cli::{CliService, GetOptions};
+use miette::Result;
+use std::collections::HashMap;
+
+/// Records reshaped so that each distinct key is a column
+#[derive(Debug, Clone)]
+pub struct Pivoted {
+    /// The index field, if any, then the distinct keys in the order they were first seen
+    pub columns: Vec<String>,
+    /// One row per distinct index value, in the order they were first seen
+    pub rows: Vec<Vec<AvroValue>>,
+}
+
+/// Pivot key/value records into a wide table with one column per distinct key.
+///
+/// Records are grouped by the value of `index_field`, or all into a single row without
+/// one. When a group has several values for a key, the last one wins. Keys a group
+/// doesn't have are `N/A`.
+pub fn pivot(
+    avro: &mut CliService,
+    key_field: &str,
+    value_field: &str,
+    index_field: Option<&str>,
+    options: &GetOptions,
+) -> Result<Pivoted> {
+    let fields: Vec<String> = [Some(key_field), Some(value_field), index_field]
+        .into_iter()
+        .flatten()
+        .map(str::to_owned)
+        .collect();
+
+    let mut keys: Vec<String> = Vec::new();
+    let mut key_columns: HashMap<String, usize> = HashMap::new();
+    let mut groups: Vec<(AvroValue, HashMap<usize, AvroValue>)> = Vec::new();
+    let mut group_rows: HashMap<String, usize> = HashMap::new();
+    for row in avro.get_fields(&fields, options)? {
+        let mut row = row.into_iter().map(|value| value.value().clone());
+        let key = row.next().unwrap_or(AvroValue::Na).to_string();
+        let value = row.next().unwrap_or(AvroValue::Na);
+        let index = row.next().unwrap_or(AvroValue::Na);
+
+        let column = *key_columns.entry(key.clone()).or_insert_with(|| {
+            keys.push(key);
+            keys.len() - 1
+        });
+        let group = *group_rows.entry(index.to_string()).or_insert_with(|| {
+            groups.push((index, HashMap::new()));
+            groups.len() - 1
+        });
+        groups[group].1.insert(column, value);
+    }
+
+    let columns = index_field
+        .map(str::to_owned)
+        .into_iter()
+        .chain(keys.iter().cloned())
+        .collect();
+    let rows = groups
+        .into_iter()
+        .map(|(index, mut values)| {
+            index_field
+                .map(|_| index)
+                .into_iter()
+                .chain(
+                    (0..keys.len()).map(|column| values.remove(&column).unwrap_or(AvroValue::Na)),
+                )
+                .collect()
+        })
+        .collect();
+    Ok(Pivoted { columns, rows })
+}
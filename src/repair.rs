@@ -0,0 +1,187 @@
+use crate::container::{find, read_block, BadBlock, Block, Header, SYNC_LEN};
+use apache_avro::{from_avro_datum, Codec, Writer};
+use miette::{IntoDiagnostic, Result, WrapErr as _};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write as _};
+use std::path::Path;
+
+/// What a repair kept and dropped
+#[derive(Debug, Clone, Default)]
+pub struct RepairReport {
+    pub records_kept: u64,
+    pub blocks_kept: u64,
+    /// Records declared by dropped blocks. Blocks too damaged to tell how many records
+    /// they held aren't counted, so this is a lower bound
+    pub records_dropped: u64,
+    pub blocks_dropped: u64,
+    /// Bytes skipped to find the next block after a damaged one
+    pub bytes_skipped: u64,
+}
+
+/// Rewrite an Avro container file, leaving out the blocks that can't be decoded
+///
+/// Blocks are read one by one. When a block is damaged, reading resumes after the next
+/// sync marker. The salvaged records are written with the original schema and metadata,
+/// so the header must be intact.
+///
+/// # Arguments
+/// * `input` - Path of the damaged file
+/// * `output` - Path to write the repaired file to
+/// * `codec` - Codec to compress the repaired file with, by default the original one
+pub fn repair(input: &Path, output: &Path, codec: Option<Codec>) -> Result<RepairReport> {
+    let bytes = fs::read(input)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Could not read file {}", input.display()))?;
+    let header = Header::parse(&bytes)
+        .wrap_err_with(|| format!("Could not read the header of {}", input.display()))?;
+
+    let file = File::create(output)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Could not create file {}", output.display()))?;
+    let codec = codec.unwrap_or(header.codec);
+    let mut writer = Writer::with_codec(&header.schema, BufWriter::new(file), codec);
+    for (key, value) in &header.user_metadata {
+        writer
+            .add_user_metadata(key.clone(), value)
+            .into_diagnostic()?;
+    }
+
+    let mut report = RepairReport::default();
+    let mut pos = header.len;
+    while pos < bytes.len() {
+        match decode_block(&bytes, pos, &header) {
+            Ok((records, next)) => {
+                report.blocks_kept += 1;
+                report.records_kept += records.len() as u64;
+                for record in records {
+                    writer
+                        .append(record)
+                        .into_diagnostic()
+                        .wrap_err("Could not write record")?;
+                }
+                pos = next;
+            }
+            Err(BadBlock { declared_records }) => {
+                report.blocks_dropped += 1;
+                report.records_dropped += declared_records.unwrap_or(0);
+                match find(&bytes[pos + 1..], &header.sync) {
+                    Some(offset) => {
+                        report.bytes_skipped += (offset + 1) as u64;
+                        pos += 1 + offset + SYNC_LEN;
+                    }
+                    None => {
+                        report.bytes_skipped += (bytes.len() - pos) as u64;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    writer
+        .into_inner()
+        .into_diagnostic()?
+        .flush()
+        .into_diagnostic()?;
+    Ok(report)
+}
+
+/// Decode the records of the block at `pos`, returning them and where the next block starts
+fn decode_block(
+    bytes: &[u8],
+    pos: usize,
+    header: &Header,
+) -> std::result::Result<(Vec<apache_avro::types::Value>, usize), BadBlock> {
+    let Block {
+        count, data, next, ..
+    } = read_block(bytes, pos, header)?;
+    let mut data = &data[..];
+    let mut records = Vec::with_capacity(count.min(1024) as usize);
+    for _ in 0..count {
+        match from_avro_datum(&header.schema, &mut data, None) {
+            Ok(record) => records.push(record),
+            Err(_) => {
+                return Err(BadBlock {
+                    declared_records: Some(count),
+                })
+            }
+        }
+    }
+    Ok((records, next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use apache_avro::Reader;
+
+    /// bttf.avro with its block of 4 records repeated `times`, and where each block and its
+    /// data start
+    fn bttf_blocks(times: usize) -> (Vec<u8>, Vec<(usize, usize)>) {
+        let bttf = fs::read("./test_assets/bttf.avro").unwrap();
+        let header = Header::parse(&bttf).unwrap();
+        let block = match read_block(&bttf, header.len, &header) {
+            Ok(block) => block,
+            Err(_) => panic!("bttf.avro has a damaged block"),
+        };
+        assert_eq!(block.next, bttf.len());
+
+        let mut bytes = bttf[..header.len].to_vec();
+        let mut blocks = Vec::new();
+        for _ in 0..times {
+            blocks.push((bytes.len(), bytes.len() + block.start - header.len));
+            bytes.extend_from_slice(&bttf[header.len..]);
+        }
+        (bytes, blocks)
+    }
+
+    /// Repair a file, returning the report and the number of records the output has
+    fn repair_bytes(bytes: &[u8]) -> (RepairReport, usize) {
+        let dir = TempDir::new("repair");
+        let input = dir.path().join("damaged.avro");
+        let output = dir.path().join("repaired.avro");
+        fs::write(&input, bytes).unwrap();
+        let report = repair(&input, &output, None).unwrap();
+        let records = Reader::new(File::open(&output).unwrap())
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap()
+            .len();
+        (report, records)
+    }
+
+    #[test]
+    fn test_repair_truncated_block() {
+        let (bytes, blocks) = bttf_blocks(2);
+        // Cut in the middle of the last block's data
+        let (report, records) = repair_bytes(&bytes[..blocks[1].1 + 10]);
+        assert_eq!(records, 4);
+        assert_eq!(report.records_kept, 4);
+        assert_eq!(report.blocks_dropped, 1);
+    }
+
+    #[test]
+    fn test_repair_bad_sync_marker() {
+        let (mut bytes, blocks) = bttf_blocks(3);
+        // The last byte of the middle block's marker
+        bytes[blocks[2].0 - 1] ^= 0xff;
+        let (report, records) = repair_bytes(&bytes);
+        // The next good marker is the last block's, so that one is lost too
+        assert_eq!(records, 4);
+        assert_eq!(report.blocks_kept, 1);
+        assert_eq!(report.blocks_dropped, 1);
+    }
+
+    #[test]
+    fn test_repair_corrupt_middle_block() {
+        let (mut bytes, blocks) = bttf_blocks(3);
+        // A reserved deflate block type, which can't be decompressed
+        bytes[blocks[1].1] = 0xff;
+        let (report, records) = repair_bytes(&bytes);
+        assert_eq!(records, 8);
+        assert_eq!(report.records_kept, 8);
+        assert_eq!(report.blocks_dropped, 1);
+        assert_eq!(report.records_dropped, 4);
+    }
+}
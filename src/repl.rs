@@ -0,0 +1,139 @@
+use crate::{print_as_table, TableAlign, TableOptions};
+use explore_avro::filter::RecordFilter;
+use explore_avro::{schema, CliService, FieldOrder, FormatOptions, GetOptions};
+use miette::{IntoDiagnostic as _, Result};
+use prettytable::color;
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+/// Records shown at a time unless changed with `take`, so that large files stay quick
+const DEFAULT_TAKE: u32 = 20;
+
+const HELP: &str = "\
+fields a,b,c   Show only these fields, or every field with no names
+filter EXPR    Only show records the expression is true for, or all records with no expression
+take N         Show at most N records
+paths          List the path of every field in the schema
+show           Show the records again (or press Enter)
+help           Show this help
+quit           Leave (or press Ctrl-D)";
+
+/// What the table currently shows
+struct View {
+    fields: Vec<String>,
+    filter: Option<RecordFilter>,
+    take: u32,
+}
+
+/// Read commands that change which fields and records are shown, printing the table again
+/// after each one. The files stay open between commands.
+pub(crate) fn run(mut avro: CliService) -> Result<()> {
+    let mut editor = DefaultEditor::new().into_diagnostic()?;
+    let mut view = View {
+        fields: Vec::new(),
+        filter: None,
+        take: DEFAULT_TAKE,
+    };
+    println!("Type `help` for the commands");
+    show(&mut avro, &view)?;
+
+    loop {
+        let line = match editor.readline("ravro> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err).into_diagnostic(),
+        };
+        let line = line.trim();
+        if !line.is_empty() {
+            let _ = editor.add_history_entry(line);
+        }
+
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        let argument = argument.trim();
+        let result = match command {
+            "" | "show" => show(&mut avro, &view),
+            "fields" => {
+                view.fields = argument
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|field| !field.is_empty())
+                    .map(str::to_owned)
+                    .collect();
+                show(&mut avro, &view)
+            }
+            "filter" if argument.is_empty() => {
+                view.filter = None;
+                show(&mut avro, &view)
+            }
+            "filter" => RecordFilter::parse(argument).and_then(|filter| {
+                view.filter = Some(filter);
+                show(&mut avro, &view)
+            }),
+            "take" => match argument.parse() {
+                Ok(take) => {
+                    view.take = take;
+                    show(&mut avro, &view)
+                }
+                Err(_) => {
+                    eprintln!("Expected a number of records, got `{}`", argument);
+                    Ok(())
+                }
+            },
+            "paths" => avro.writer_schema().and_then(|writer_schema| {
+                for path in schema::leaf_paths(&writer_schema)? {
+                    println!("{}", path);
+                }
+                Ok(())
+            }),
+            "help" => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            "quit" | "exit" => break,
+            _ => {
+                eprintln!(
+                    "Unknown command `{}`, type `help` for the commands",
+                    command
+                );
+                Ok(())
+            }
+        };
+        // Mistakes are expected while exploring, so they don't end the session
+        if let Err(err) = result {
+            eprintln!("{:?}", err);
+        }
+    }
+    Ok(())
+}
+
+fn show(avro: &mut CliService, view: &View) -> Result<()> {
+    let fields = if view.fields.is_empty() {
+        avro.get_field_names_across_files(FieldOrder::Schema)?
+    } else {
+        view.fields.clone()
+    };
+    let options = GetOptions {
+        take: Some(view.take),
+        record_filter: view.filter.clone(),
+        ..GetOptions::default()
+    };
+    let data = avro.get_fields(&fields, &options)?;
+    print_as_table(
+        None,
+        &options.columns(&fields),
+        data,
+        &[],
+        usize::MAX,
+        &FormatOptions::default(),
+        &TableOptions {
+            title: None,
+            header_color: color::BLUE,
+            highlight_color: color::GREEN,
+            na_color: color::RED,
+            wrap: None,
+            align: TableAlign::Left,
+            paged: false,
+        },
+    )?;
+    Ok(())
+}
@@ -0,0 +1,788 @@
+use crate::field_path::{self, Segment};
+use apache_avro::schema::{
+    ArraySchema, DecimalSchema, EnumSchema, FixedSchema, MapSchema, Name, NamesRef, RecordSchema,
+    ResolvedSchema, Schema, UnionSchema,
+};
+use apache_avro::types::Value;
+use apache_avro::BigDecimal;
+use miette::{bail, IntoDiagnostic, Result, WrapErr as _};
+use num_bigint::BigInt;
+use std::collections::{BTreeMap, HashSet};
+
+/// Get every leaf field path of a schema, in declaration order.
+/// Nested records are descended into and joined with dots, e.g. `address.geo.lat`.
+/// Arrays, maps and other non-record types are leaves.
+pub fn leaf_paths(schema: &Schema) -> Result<Vec<String>> {
+    let resolved = ResolvedSchema::try_from(schema).into_diagnostic()?;
+    let mut paths = Vec::new();
+    collect_leaf_paths(
+        schema,
+        resolved.get_names(),
+        "",
+        &mut Vec::new(),
+        &mut paths,
+    );
+
+    let mut unique = Vec::with_capacity(paths.len());
+    for path in paths {
+        if !unique.contains(&path) {
+            unique.push(path);
+        }
+    }
+    Ok(unique)
+}
+
+/// Build a record schema with only the given top-level fields, in that order.
+/// Field schemas are kept whole, including logical types. Named types defined in
+/// fields that are left out are defined again where the kept fields use them.
+pub fn project(schema: &Schema, fields: &[String]) -> Result<Schema> {
+    let resolved = ResolvedSchema::try_from(schema).into_diagnostic()?;
+    let names = resolved.get_names();
+    let Schema::Record(record) = resolve(schema, names) else {
+        bail!("Only fields of a record schema can be projected");
+    };
+
+    let mut defined = HashSet::from([record.name.clone()]);
+    let mut projected = Vec::with_capacity(fields.len());
+    for (position, name) in fields.iter().enumerate() {
+        let Some(&index) = record.lookup.get(name) else {
+            bail!("Field `{}` is not in the schema", name);
+        };
+        let mut field = record.fields[index].clone();
+        field.schema = inline_refs(&field.schema, names, &mut defined)?;
+        field.position = position;
+        projected.push(field);
+    }
+
+    let lookup: BTreeMap<String, usize> = projected
+        .iter()
+        .map(|field| (field.name.clone(), field.position))
+        .collect();
+    Ok(Schema::Record(RecordSchema {
+        fields: projected,
+        lookup,
+        ..record.clone()
+    }))
+}
+
+/// The fields of a record schema whose type is one of `types`, in the order given. Fields
+/// are top-level names or paths like `address.city` or `items[2].name`, as parsed by
+/// `field_path::parse`, and are looked up ignoring case if `ignore_case` is set.
+///
+/// Types are named as by `type_name`, e.g. `string`, `timestamp-millis` or the name of a
+/// record. A union matches when one of its branches does, so `string` also selects optional
+/// strings, and paths go through every branch of the unions on their way.
+pub fn fields_of_types<'f>(
+    schema: &Schema,
+    fields: &'f [String],
+    types: &[String],
+    ignore_case: bool,
+) -> Result<Vec<&'f String>> {
+    let resolved = ResolvedSchema::try_from(schema).into_diagnostic()?;
+    let names = resolved.get_names();
+    if !matches!(resolve(schema, names), Schema::Record(_)) {
+        bail!("Only fields of a record schema can be selected by type");
+    }
+
+    let matches = |schema: &Schema| types.contains(&type_name(resolve(schema, names)));
+    Ok(fields
+        .iter()
+        .filter(|field| {
+            schemas_at_path(schema, names, &field_path::parse(field), ignore_case)
+                .into_iter()
+                .any(|schema| match resolve(schema, names) {
+                    Schema::Union(union) => matches(schema) || union.variants().iter().any(matches),
+                    resolved => matches(resolved),
+                })
+        })
+        .collect())
+}
+
+/// The schemas a path can lead to, several when it goes through unions
+fn schemas_at_path<'s>(
+    schema: &'s Schema,
+    names: &NamesRef<'s>,
+    path: &[Segment],
+    ignore_case: bool,
+) -> Vec<&'s Schema> {
+    let mut schemas = vec![schema];
+    for segment in path {
+        schemas = schemas
+            .into_iter()
+            .flat_map(|schema| match resolve(schema, names) {
+                Schema::Union(union) => union.variants().iter().collect(),
+                schema => vec![schema],
+            })
+            .filter_map(|schema| match (resolve(schema, names), segment) {
+                (Schema::Record(RecordSchema { fields, .. }), Segment::Field(name)) => fields
+                    .iter()
+                    .find(|field| {
+                        if ignore_case {
+                            field.name.eq_ignore_ascii_case(name)
+                        } else {
+                            field.name == *name
+                        }
+                    })
+                    .map(|field| &field.schema),
+                (Schema::Map(MapSchema { types, .. }), Segment::Field(_)) => Some(&**types),
+                (Schema::Array(ArraySchema { items, .. }), Segment::Index(_)) => Some(&**items),
+                _ => None,
+            })
+            .collect();
+    }
+    schemas
+}
+
+/// The name of a schema's type as written in schemas: the name of a named type,
+/// or else the primitive or logical type, like `string` or `timestamp-millis`
+pub fn type_name(schema: &Schema) -> String {
+    match schema {
+        Schema::Record(RecordSchema { name, .. })
+        | Schema::Enum(EnumSchema { name, .. })
+        | Schema::Fixed(FixedSchema { name, .. })
+        | Schema::Ref { name } => name.name.clone(),
+        Schema::Null => "null".to_owned(),
+        Schema::Boolean => "boolean".to_owned(),
+        Schema::Int => "int".to_owned(),
+        Schema::Long => "long".to_owned(),
+        Schema::Float => "float".to_owned(),
+        Schema::Double => "double".to_owned(),
+        Schema::Bytes => "bytes".to_owned(),
+        Schema::String => "string".to_owned(),
+        Schema::Array(_) => "array".to_owned(),
+        Schema::Map(_) => "map".to_owned(),
+        Schema::Union(_) => "union".to_owned(),
+        Schema::Decimal(_) => "decimal".to_owned(),
+        Schema::BigDecimal => "big-decimal".to_owned(),
+        Schema::Uuid => "uuid".to_owned(),
+        Schema::Date => "date".to_owned(),
+        Schema::TimeMillis => "time-millis".to_owned(),
+        Schema::TimeMicros => "time-micros".to_owned(),
+        Schema::TimestampMillis => "timestamp-millis".to_owned(),
+        Schema::TimestampMicros => "timestamp-micros".to_owned(),
+        Schema::TimestampNanos => "timestamp-nanos".to_owned(),
+        Schema::LocalTimestampMillis => "local-timestamp-millis".to_owned(),
+        Schema::LocalTimestampMicros => "local-timestamp-micros".to_owned(),
+        Schema::LocalTimestampNanos => "local-timestamp-nanos".to_owned(),
+        Schema::Duration => "duration".to_owned(),
+    }
+}
+
+/// Follow a named reference to its definition
+pub(crate) fn resolve<'s>(schema: &'s Schema, names: &NamesRef<'s>) -> &'s Schema {
+    match schema {
+        Schema::Ref { name } => names.get(name).copied().unwrap_or(schema),
+        _ => schema,
+    }
+}
+
+/// Check that every enum in a value holds the symbol its schema declares at that index.
+/// A mismatch means the data was written with a different schema than it claims.
+pub(crate) fn check_enums<'s>(
+    value: &Value,
+    schema: &'s Schema,
+    names: &NamesRef<'s>,
+) -> Result<()> {
+    match (value, resolve(schema, names)) {
+        (Value::Enum(id, desc), Schema::Enum(EnumSchema { name, symbols, .. })) => {
+            match symbols.get(*id as usize) {
+                Some(symbol) if symbol == desc => Ok(()),
+                Some(symbol) => bail!(
+                    "Enum {} declares `{}` at index {} but the value is `{}`",
+                    name,
+                    symbol,
+                    id,
+                    desc
+                ),
+                None => bail!(
+                    "Enum {} has no symbol at index {} (the value is `{}`)",
+                    name,
+                    id,
+                    desc
+                ),
+            }
+        }
+        (
+            Value::Record(fields),
+            Schema::Record(RecordSchema {
+                fields: schema_fields,
+                lookup,
+                ..
+            }),
+        ) => {
+            for (field_name, field_value) in fields {
+                if let Some(field) = lookup.get(field_name).map(|&i| &schema_fields[i]) {
+                    check_enums(field_value, &field.schema, names)
+                        .wrap_err_with(|| format!("In field `{}`", field_name))?;
+                }
+            }
+            Ok(())
+        }
+        (Value::Union(idx, inner), Schema::Union(union)) => {
+            match union.variants().get(*idx as usize) {
+                Some(variant) => check_enums(inner, variant, names),
+                None => bail!("Union has no branch at index {}", idx),
+            }
+        }
+        (
+            Value::Array(items),
+            Schema::Array(ArraySchema {
+                items: item_schema, ..
+            }),
+        ) => items
+            .iter()
+            .try_for_each(|item| check_enums(item, item_schema, names)),
+        (Value::Map(entries), Schema::Map(MapSchema { types, .. })) => entries
+            .values()
+            .try_for_each(|entry| check_enums(entry, types, names)),
+        _ => Ok(()),
+    }
+}
+
+/// Whether a schema has a fixed-scale decimal anywhere in it
+pub(crate) fn has_decimals(schema: &Schema) -> bool {
+    match schema {
+        Schema::Decimal(_) => true,
+        Schema::Record(RecordSchema { fields, .. }) => {
+            fields.iter().any(|field| has_decimals(&field.schema))
+        }
+        Schema::Array(ArraySchema { items, .. }) => has_decimals(items),
+        Schema::Map(MapSchema { types, .. }) => has_decimals(types),
+        Schema::Union(union) => union.variants().iter().any(has_decimals),
+        // Named types are checked where they are defined
+        _ => false,
+    }
+}
+
+/// Give every decimal in a value the scale its schema declares, as a `BigDecimal`. Decimals
+/// are decoded as their unscaled integer, so `123.45` in a `decimal(5,2)` reads as `12345`.
+pub(crate) fn scale_decimals<'s>(value: Value, schema: &'s Schema, names: &NamesRef<'s>) -> Value {
+    match (value, resolve(schema, names)) {
+        (Value::Decimal(decimal), Schema::Decimal(DecimalSchema { scale, .. })) => {
+            Value::BigDecimal(BigDecimal::new(BigInt::from(decimal), *scale as i64))
+        }
+        (Value::Union(index, inner), Schema::Union(union)) => {
+            match union.variants().get(index as usize) {
+                Some(variant) => {
+                    Value::Union(index, Box::new(scale_decimals(*inner, variant, names)))
+                }
+                None => Value::Union(index, inner),
+            }
+        }
+        (
+            Value::Record(fields),
+            Schema::Record(RecordSchema {
+                fields: schema_fields,
+                lookup,
+                ..
+            }),
+        ) => Value::Record(
+            fields
+                .into_iter()
+                .map(|(name, value)| {
+                    let value = match lookup.get(&name) {
+                        Some(&i) => scale_decimals(value, &schema_fields[i].schema, names),
+                        None => value,
+                    };
+                    (name, value)
+                })
+                .collect(),
+        ),
+        (
+            Value::Array(items),
+            Schema::Array(ArraySchema {
+                items: item_schema, ..
+            }),
+        ) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| scale_decimals(item, item_schema, names))
+                .collect(),
+        ),
+        (Value::Map(entries), Schema::Map(MapSchema { types, .. })) => Value::Map(
+            entries
+                .into_iter()
+                .map(|(key, entry)| (key, scale_decimals(entry, types, names)))
+                .collect(),
+        ),
+        (value, _) => value,
+    }
+}
+
+/// Wrap the value of every union in a record with a single field named after the branch it
+/// holds, like `{"long": 5}` in Avro's JSON encoding. The union is kept around it, so that
+/// `FormatOptions::annotated_unions` can tell it from other records. Nulls stay null.
+pub(crate) fn annotate_unions<'s>(value: Value, schema: &'s Schema, names: &NamesRef<'s>) -> Value {
+    match (value, resolve(schema, names)) {
+        (Value::Union(index, inner), Schema::Union(union)) => {
+            match union.variants().get(index as usize) {
+                Some(variant) => match annotate_unions(*inner, variant, names) {
+                    Value::Null => Value::Null,
+                    inner => Value::Union(
+                        index,
+                        Box::new(Value::Record(vec![(type_name(variant), inner)])),
+                    ),
+                },
+                None => Value::Union(index, inner),
+            }
+        }
+        (
+            Value::Record(fields),
+            Schema::Record(RecordSchema {
+                fields: schema_fields,
+                lookup,
+                ..
+            }),
+        ) => Value::Record(
+            fields
+                .into_iter()
+                .map(|(name, value)| {
+                    let value = match lookup.get(&name) {
+                        Some(&i) => annotate_unions(value, &schema_fields[i].schema, names),
+                        None => value,
+                    };
+                    (name, value)
+                })
+                .collect(),
+        ),
+        (
+            Value::Array(items),
+            Schema::Array(ArraySchema {
+                items: item_schema, ..
+            }),
+        ) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| annotate_unions(item, item_schema, names))
+                .collect(),
+        ),
+        (Value::Map(entries), Schema::Map(MapSchema { types, .. })) => Value::Map(
+            entries
+                .into_iter()
+                .map(|(key, entry)| (key, annotate_unions(entry, types, names)))
+                .collect(),
+        ),
+        (value, _) => value,
+    }
+}
+
+/// Logical types apache-avro decodes into their own `Value` variants
+const LOGICAL_TYPES: &[&str] = &[
+    "decimal",
+    "big-decimal",
+    "uuid",
+    "date",
+    "time-millis",
+    "time-micros",
+    "timestamp-millis",
+    "timestamp-micros",
+    "timestamp-nanos",
+    "local-timestamp-millis",
+    "local-timestamp-micros",
+    "local-timestamp-nanos",
+    "duration",
+];
+
+/// A logical type declared on a top-level field in the JSON of a schema
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct DeclaredLogicalType {
+    pub(crate) field: String,
+    /// For a union field, the branch that declares it
+    pub(crate) branch: Option<usize>,
+    pub(crate) logical_type: String,
+}
+
+impl DeclaredLogicalType {
+    /// The base type a value of the field was decoded as instead of the logical type, which
+    /// happens when the annotation is malformed, e.g. `timestamp-millis` on an int
+    pub(crate) fn lost_by(&self, value: &Value) -> Option<&'static str> {
+        let value = match (self.branch, value) {
+            (Some(branch), Value::Union(index, value)) if *index as usize == branch => value,
+            (Some(_), _) => return None,
+            (None, value) => value,
+        };
+        match value {
+            Value::Int(_) => Some("int"),
+            Value::Long(_) => Some("long"),
+            Value::Bytes(_) => Some("bytes"),
+            Value::Fixed(..) => Some("fixed"),
+            Value::String(_) => Some("string"),
+            _ => None,
+        }
+    }
+}
+
+/// The logical types known to apache-avro that the top-level fields of a record schema
+/// declare, read from its JSON since a parsed schema no longer has the ones that were dropped
+pub(crate) fn declared_logical_types(schema_json: &serde_json::Value) -> Vec<DeclaredLogicalType> {
+    let logical_type = |field_type: &serde_json::Value| {
+        field_type
+            .get("logicalType")
+            .and_then(|logical_type| logical_type.as_str())
+            .filter(|logical_type| LOGICAL_TYPES.contains(logical_type))
+            .map(str::to_owned)
+    };
+    let fields = schema_json
+        .get("fields")
+        .and_then(|fields| fields.as_array());
+    let mut declared = Vec::new();
+    for field in fields.into_iter().flatten() {
+        let (Some(name), Some(field_type)) = (
+            field.get("name").and_then(|name| name.as_str()),
+            field.get("type"),
+        ) else {
+            continue;
+        };
+        match field_type {
+            serde_json::Value::Array(branches) => {
+                for (branch, branch_type) in branches.iter().enumerate() {
+                    if let Some(logical_type) = logical_type(branch_type) {
+                        declared.push(DeclaredLogicalType {
+                            field: name.to_owned(),
+                            branch: Some(branch),
+                            logical_type,
+                        });
+                    }
+                }
+            }
+            field_type => {
+                if let Some(logical_type) = logical_type(field_type) {
+                    declared.push(DeclaredLogicalType {
+                        field: name.to_owned(),
+                        branch: None,
+                        logical_type,
+                    });
+                }
+            }
+        }
+    }
+    declared
+}
+
+/// Copy a schema, replacing references to types not yet `defined` by their definitions
+fn inline_refs(
+    schema: &Schema,
+    names: &NamesRef<'_>,
+    defined: &mut HashSet<Name>,
+) -> Result<Schema> {
+    Ok(match schema {
+        Schema::Ref { name } if !defined.contains(name) => match names.get(name) {
+            Some(definition) => inline_refs(definition, names, defined)?,
+            None => bail!("Type {} is referenced but not defined", name),
+        },
+        Schema::Record(record) => {
+            defined.insert(record.name.clone());
+            let mut record = record.clone();
+            for field in &mut record.fields {
+                field.schema = inline_refs(&field.schema, names, defined)?;
+            }
+            Schema::Record(record)
+        }
+        Schema::Enum(EnumSchema { name, .. }) | Schema::Fixed(FixedSchema { name, .. }) => {
+            defined.insert(name.clone());
+            schema.clone()
+        }
+        Schema::Union(union) => Schema::Union(
+            UnionSchema::new(
+                union
+                    .variants()
+                    .iter()
+                    .map(|variant| inline_refs(variant, names, defined))
+                    .collect::<Result<_>>()?,
+            )
+            .into_diagnostic()?,
+        ),
+        Schema::Array(array) => Schema::Array(ArraySchema {
+            items: Box::new(inline_refs(&array.items, names, defined)?),
+            ..array.clone()
+        }),
+        Schema::Map(map) => Schema::Map(MapSchema {
+            types: Box::new(inline_refs(&map.types, names, defined)?),
+            ..map.clone()
+        }),
+        _ => schema.clone(),
+    })
+}
+
+fn collect_leaf_paths<'s>(
+    schema: &'s Schema,
+    names: &NamesRef<'s>,
+    prefix: &str,
+    // Records currently being descended into, to stop at recursive types
+    seen: &mut Vec<&'s Name>,
+    paths: &mut Vec<String>,
+) {
+    match resolve(schema, names) {
+        Schema::Record(RecordSchema { name, fields, .. }) if !seen.contains(&name) => {
+            seen.push(name);
+            for field in fields {
+                let path = if prefix.is_empty() {
+                    field.name.clone()
+                } else {
+                    format!("{}.{}", prefix, field.name)
+                };
+                collect_leaf_paths(&field.schema, names, &path, seen, paths);
+            }
+            seen.pop();
+        }
+        Schema::Union(union) => {
+            let mut has_record_variant = false;
+            let mut has_leaf_variant = false;
+            for variant in union.variants() {
+                match resolve(variant, names) {
+                    Schema::Null => {}
+                    Schema::Record(_) => {
+                        has_record_variant = true;
+                        collect_leaf_paths(variant, names, prefix, seen, paths);
+                    }
+                    _ => has_leaf_variant = true,
+                }
+            }
+            if (has_leaf_variant || !has_record_variant) && !prefix.is_empty() {
+                paths.push(prefix.to_owned());
+            }
+        }
+        _ => {
+            if !prefix.is_empty() {
+                paths.push(prefix.to_owned());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::avro_value::{format_value, to_json, FormatOptions};
+    use apache_avro::{Reader, Writer};
+
+    #[test]
+    fn test_fields_of_types() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "order", "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "note", "type": ["null", "string"]},
+                {"name": "address", "type": {"type": "record", "name": "address", "fields": [
+                    {"name": "city", "type": "string"}
+                ]}},
+                {"name": "items", "type": {"type": "array", "items": {
+                    "type": "record", "name": "item", "fields": [
+                        {"name": "name", "type": "string"},
+                        {"name": "price", "type": "double"}
+                    ]
+                }}}
+            ]}"#,
+        )
+        .unwrap();
+        let fields: Vec<String> = [
+            "id",
+            "note",
+            "address.city",
+            "items[2].name",
+            "items[0].price",
+        ]
+        .map(String::from)
+        .to_vec();
+        let strings = vec!["string".to_owned()];
+        assert_eq!(
+            fields_of_types(&schema, &fields, &strings, false).unwrap(),
+            vec!["note", "address.city", "items[2].name"]
+        );
+
+        let fields = vec!["ADDRESS.City".to_owned()];
+        assert!(fields_of_types(&schema, &fields, &strings, false)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            fields_of_types(&schema, &fields, &strings, true).unwrap(),
+            vec!["ADDRESS.City"]
+        );
+    }
+
+    #[test]
+    fn test_annotate_unions() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "event", "fields": [
+                {"name": "id", "type": ["null", "long", "string"]},
+                {"name": "tags", "type": {"type": "array", "items": ["null", "string"]}}
+            ]}"#,
+        )
+        .unwrap();
+        let resolved = ResolvedSchema::try_from(&schema).unwrap();
+        let annotated = |index, branch: &str, value| {
+            Value::Union(
+                index,
+                Box::new(Value::Record(vec![(branch.to_owned(), value)])),
+            )
+        };
+        let record = Value::Record(vec![
+            ("id".to_owned(), Value::Union(1, Box::new(Value::Long(5)))),
+            (
+                "tags".to_owned(),
+                Value::Array(vec![
+                    Value::Union(0, Box::new(Value::Null)),
+                    Value::Union(1, Box::new(Value::String("a".to_owned()))),
+                ]),
+            ),
+        ]);
+        assert_eq!(
+            annotate_unions(record, &schema, resolved.get_names()),
+            Value::Record(vec![
+                ("id".to_owned(), annotated(1, "long", Value::Long(5))),
+                (
+                    "tags".to_owned(),
+                    Value::Array(vec![
+                        Value::Null,
+                        annotated(1, "string", Value::String("a".to_owned())),
+                    ])
+                ),
+            ])
+        );
+
+        let options = FormatOptions {
+            annotated_unions: true,
+            ..FormatOptions::default()
+        };
+        let id = annotated(1, "long", Value::Long(5));
+        assert_eq!(format_value(&id, &options).unwrap(), "long=5");
+        assert_eq!(
+            to_json(&id, &options).unwrap(),
+            serde_json::json!({"long": 5})
+        );
+    }
+
+    #[test]
+    fn test_scale_decimals() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "payment", "fields": [
+                {"name": "amount", "type": ["null", {
+                    "type": "bytes", "logicalType": "decimal", "precision": 5, "scale": 2
+                }]}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(has_decimals(&schema));
+        let resolved = ResolvedSchema::try_from(&schema).unwrap();
+        let record = Value::Record(vec![(
+            "amount".to_owned(),
+            Value::Union(1, Box::new(Value::Decimal(vec![0x30, 0x39].into()))),
+        )]);
+        let Value::Record(fields) = scale_decimals(record, &schema, resolved.get_names()) else {
+            panic!("expected a record");
+        };
+        let options = FormatOptions {
+            decimal_as_float: true,
+            ..FormatOptions::default()
+        };
+        assert_eq!(format_value(&fields[0].1, &options).unwrap(), "123.45");
+        // Exactly, as text, without the float rendering
+        let options = FormatOptions::default();
+        assert_eq!(format_value(&fields[0].1, &options).unwrap(), "123.45");
+        assert_eq!(
+            to_json(&fields[0].1, &options).unwrap(),
+            serde_json::Value::String("123.45".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_declared_logical_types() {
+        let schema_json = serde_json::json!({"type": "record", "name": "event", "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "at", "type": {"type": "int", "logicalType": "timestamp-millis"}},
+            {"name": "tag", "type": {"type": "string", "logicalType": "custom"}},
+            {"name": "seen", "type": [
+                "null", "string", {"type": "long", "logicalType": "timestamp-micros"}
+            ]}
+        ]});
+        let declared = declared_logical_types(&schema_json);
+        assert_eq!(
+            declared,
+            vec![
+                DeclaredLogicalType {
+                    field: "at".to_owned(),
+                    branch: None,
+                    logical_type: "timestamp-millis".to_owned(),
+                },
+                DeclaredLogicalType {
+                    field: "seen".to_owned(),
+                    branch: Some(2),
+                    logical_type: "timestamp-micros".to_owned(),
+                },
+            ]
+        );
+
+        assert_eq!(declared[0].lost_by(&Value::Int(1)), Some("int"));
+        assert_eq!(declared[0].lost_by(&Value::TimestampMillis(1)), None);
+        let seen = |index, value| Value::Union(index, Box::new(value));
+        assert_eq!(declared[1].lost_by(&seen(2, Value::Long(1))), Some("long"));
+        assert_eq!(
+            declared[1].lost_by(&seen(1, Value::String("x".into()))),
+            None
+        );
+        assert_eq!(
+            declared[1].lost_by(&seen(2, Value::TimestampMicros(1))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_project_keeps_logical_types() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "payment", "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "at", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+                {"name": "amount", "type": {
+                    "type": "bytes", "logicalType": "decimal", "precision": 10, "scale": 2
+                }},
+                {"name": "settled", "type": [
+                    "null", {"type": "long", "logicalType": "timestamp-millis"}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+        let projected = project(
+            &schema,
+            &["settled".to_owned(), "at".to_owned(), "amount".to_owned()],
+        )
+        .unwrap();
+        // Schemas compare by canonical form, which leaves logical types out
+        let field_types = |schema: &Schema| -> Vec<String> {
+            let Schema::Record(record) = schema else {
+                panic!("schema is not a record");
+            };
+            record
+                .fields
+                .iter()
+                .map(|field| match &field.schema {
+                    Schema::Union(union) => union
+                        .variants()
+                        .iter()
+                        .map(type_name)
+                        .collect::<Vec<_>>()
+                        .join("|"),
+                    schema => type_name(schema),
+                })
+                .collect()
+        };
+        let expected = vec!["null|timestamp-millis", "timestamp-millis", "decimal"];
+        assert_eq!(field_types(&projected), expected);
+
+        // Written and read back, the timestamp is still a timestamp rather than a long
+        let mut writer = Writer::new(&projected, Vec::new());
+        writer
+            .append(Value::Record(vec![
+                ("settled".to_owned(), Value::Union(0, Box::new(Value::Null))),
+                ("at".to_owned(), Value::TimestampMillis(1_700_000_000_000)),
+                ("amount".to_owned(), Value::Decimal(vec![0x30, 0x39].into())),
+            ]))
+            .unwrap();
+        let bytes = writer.into_inner().unwrap();
+        let reader = Reader::new(&bytes[..]).unwrap();
+        assert_eq!(field_types(reader.writer_schema()), expected);
+        let records: Vec<Value> = reader.map(|record| record.unwrap()).collect();
+        let Value::Record(fields) = &records[0] else {
+            panic!("read value is not a record");
+        };
+        assert_eq!(fields[1].1, Value::TimestampMillis(1_700_000_000_000));
+    }
+}
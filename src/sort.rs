@@ -0,0 +1,195 @@
+use crate::avro_value::{format_value, AvroValue, FormatOptions};
+use crate::cli::AvroData;
+use apache_avro::types::Value;
+use miette::{bail, Result};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// A column to sort rows by, written `column` for ascending order or `-column` for descending
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (column, descending) = match s.strip_prefix('-') {
+            Some(column) => (column, true),
+            None => (s, false),
+        };
+        if column.is_empty() {
+            return Err("Expected a column to sort by".to_owned());
+        }
+        Ok(SortKey {
+            column: column.to_owned(),
+            descending,
+        })
+    }
+}
+
+/// Sort rows by each key in turn, keeping the order of rows that compare equal on all of them.
+///
+/// Numbers, dates and timestamps compare as numbers, strings and enums as text, and values of
+/// different types by type. Null and missing (N/A) values sort last whatever the direction.
+pub fn sort_rows(columns: &[String], data: &mut AvroData, keys: &[SortKey]) -> Result<()> {
+    if keys.is_empty() {
+        return Ok(());
+    }
+    let mut positions = Vec::with_capacity(keys.len());
+    for key in keys {
+        match columns.iter().position(|column| *column == key.column) {
+            Some(position) => positions.push((position, key.descending)),
+            None => bail!("Column `{}` to sort by is not selected", key.column),
+        }
+    }
+
+    // Work out what each row sorts by once, rather than on every comparison
+    let mut keyed: Vec<(Vec<Option<Sortable>>, _)> = data
+        .drain(..)
+        .map(|row| {
+            let sort_by = positions
+                .iter()
+                .map(|&(position, _)| row.get(position).and_then(|value| sortable(value.value())))
+                .collect();
+            (sort_by, row)
+        })
+        .collect();
+    keyed.sort_by(|(a, _), (b, _)| {
+        a.iter()
+            .zip(b)
+            .zip(&positions)
+            .map(|((a, b), &(_, descending))| match (a, b) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) if descending => b.cmp(a),
+                (Some(a), Some(b)) => a.cmp(b),
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    data.extend(keyed.into_iter().map(|(_, row)| row));
+    Ok(())
+}
+
+/// A value reduced to what it sorts by
+#[derive(Debug)]
+enum Sortable {
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl Sortable {
+    /// Values of different types sort in this order
+    fn rank(&self) -> u8 {
+        match self {
+            Sortable::Boolean(_) => 0,
+            Sortable::Integer(_) | Sortable::Float(_) => 1,
+            Sortable::Text(_) => 2,
+        }
+    }
+
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Sortable::Boolean(a), Sortable::Boolean(b)) => a.cmp(b),
+            (Sortable::Integer(a), Sortable::Integer(b)) => a.cmp(b),
+            (Sortable::Float(a), Sortable::Float(b)) => a.total_cmp(b),
+            (Sortable::Integer(a), Sortable::Float(b)) => (*a as f64).total_cmp(b),
+            (Sortable::Float(a), Sortable::Integer(b)) => a.total_cmp(&(*b as f64)),
+            (Sortable::Text(a), Sortable::Text(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+/// None for null and missing values
+fn sortable(value: &AvroValue) -> Option<Sortable> {
+    match value {
+        AvroValue::Na => None,
+        AvroValue::Value(value) => sortable_value(value),
+    }
+}
+
+fn sortable_value(value: &Value) -> Option<Sortable> {
+    Some(match value {
+        Value::Null => return None,
+        Value::Union(_, value) => return sortable_value(value),
+        Value::Boolean(b) => Sortable::Boolean(*b),
+        Value::Int(i) | Value::Date(i) | Value::TimeMillis(i) => Sortable::Integer((*i).into()),
+        Value::Long(l)
+        | Value::TimeMicros(l)
+        | Value::TimestampMillis(l)
+        | Value::TimestampMicros(l)
+        | Value::TimestampNanos(l)
+        | Value::LocalTimestampMillis(l)
+        | Value::LocalTimestampMicros(l)
+        | Value::LocalTimestampNanos(l) => Sortable::Integer(*l),
+        Value::Float(f) => Sortable::Float((*f).into()),
+        Value::Double(d) => Sortable::Float(*d),
+        Value::String(s) | Value::Enum(_, s) => Sortable::Text(s.clone()),
+        Value::Decimal(_) | Value::BigDecimal(_) => {
+            let options = FormatOptions {
+                decimal_as_float: true,
+                ..FormatOptions::default()
+            };
+            let text = format_value(value, &options).ok()?;
+            match text.parse() {
+                Ok(number) => Sortable::Float(number),
+                Err(_) => Sortable::Text(text),
+            }
+        }
+        other => Sortable::Text(format_value(other, &FormatOptions::default()).ok()?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::AvroColumnarValue;
+
+    #[test]
+    fn test_sort_rows() {
+        let columns = vec!["region".to_owned(), "age".to_owned()];
+        let row = |region: &str, age: Option<i32>| {
+            vec![
+                AvroColumnarValue::from(
+                    "region".to_owned(),
+                    AvroValue::from(Value::String(region.to_owned())),
+                ),
+                AvroColumnarValue::from(
+                    "age".to_owned(),
+                    age.map_or(AvroValue::na(), |age| AvroValue::from(Value::Int(age))),
+                ),
+            ]
+        };
+        let mut data = vec![
+            row("west", Some(30)),
+            row("east", None),
+            row("east", Some(20)),
+            row("west", Some(40)),
+            row("east", Some(50)),
+        ];
+
+        let keys: Vec<SortKey> = ["region", "-age"]
+            .iter()
+            .map(|k| k.parse().unwrap())
+            .collect();
+        sort_rows(&columns, &mut data, &keys).unwrap();
+        let sorted: Vec<String> = data
+            .iter()
+            .map(|row| format!("{} {}", row[0].value(), row[1].value()))
+            .collect();
+        assert_eq!(
+            sorted,
+            vec!["east 50", "east 20", "east N/A", "west 40", "west 30"]
+        );
+
+        assert!("-".parse::<SortKey>().is_err());
+        assert!(sort_rows(&columns, &mut data, &["name".parse().unwrap()]).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use crate::avro_value::AvroValue;
+use crate::cli::CliService;
+use apache_avro::{types::Value, Codec, Schema, Writer};
+use miette::{bail, IntoDiagnostic, Result, WrapErr as _};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write as _};
+use std::path::Path;
+
+type PartitionWriter<'a> = Writer<'a, BufWriter<File>>;
+
+/// Split all records into one Avro file per distinct value of a field
+///
+/// Files are named `<by>=<value>.avro` and written with the original schema and `codec`.
+/// Characters that aren't safe in file names are replaced by `_`, and values that end up
+/// with the same name as an earlier one get a numbered suffix, e.g. `<by>=us_east~2.avro`.
+/// At most `max_open_writers` files are kept open at once: past that, the least
+/// recently used one is closed, and later records for its value spill into an
+/// additional `<by>=<value>+<n>.avro` part.
+///
+/// # Arguments
+/// * `by` - Name of the field to partition by
+/// * `output_dir` - Directory to write the partitions into
+/// * `max_open_writers` - Maximum number of output files open at once
+/// * `codec` - Codec to compress the output files with
+pub fn split(
+    avro: &mut CliService,
+    by: &str,
+    output_dir: &Path,
+    max_open_writers: usize,
+    codec: Codec,
+) -> Result<()> {
+    fs::create_dir_all(output_dir)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Could not create directory {}", output_dir.display()))?;
+
+    let schema = avro.writer_schema()?;
+    let mut partitions = Partitions {
+        schema: &schema,
+        codec,
+        output_dir,
+        max_open: max_open_writers.max(1),
+        open: HashMap::new(),
+        lru: Vec::new(),
+        names: FileNames::new(by),
+    };
+
+    for file in avro.files_mut() {
+        let path = file.path().to_owned();
+        let reader = file.reader()?;
+        if reader.writer_schema().canonical_form() != schema.canonical_form() {
+            bail!(
+                "Cannot split {}: its schema differs from the first file",
+                path.display()
+            );
+        }
+
+        for (i, row) in reader.enumerate() {
+            let row = row
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Could not parse row {} from the Avro", i))?;
+            let key = match &row {
+                Value::Record(fields) => fields
+                    .iter()
+                    .find(|(n, _)| n == by)
+                    .map(|(_, v)| AvroValue::from(v.clone()))
+                    .unwrap_or_else(AvroValue::na),
+                _ => AvroValue::na(),
+            };
+            partitions.write(key.to_string(), row)?;
+        }
+    }
+
+    partitions.close()
+}
+
+struct Partitions<'a> {
+    schema: &'a Schema,
+    codec: Codec,
+    output_dir: &'a Path,
+    max_open: usize,
+    open: HashMap<String, PartitionWriter<'a>>,
+    /// Keys of the open writers, least recently used first
+    lru: Vec<String>,
+    names: FileNames,
+}
+
+impl<'a> Partitions<'a> {
+    fn write(&mut self, key: String, record: Value) -> Result<()> {
+        if let Some(pos) = self.lru.iter().position(|k| *k == key) {
+            let key = self.lru.remove(pos);
+            self.lru.push(key);
+        } else {
+            if self.open.len() >= self.max_open {
+                let evicted = self.lru.remove(0);
+                if let Some(writer) = self.open.remove(&evicted) {
+                    finish(writer)?;
+                }
+            }
+            let writer = self.create(&key)?;
+            self.open.insert(key.clone(), writer);
+            self.lru.push(key.clone());
+        }
+
+        self.open
+            .get_mut(&key)
+            .expect("writer for key was just opened")
+            .append(record)
+            .into_diagnostic()
+            .wrap_err("Could not write record")?;
+        Ok(())
+    }
+
+    fn create(&mut self, key: &str) -> Result<PartitionWriter<'a>> {
+        let path = self.output_dir.join(self.names.next(key));
+        let file = File::create(&path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Could not create file {}", path.display()))?;
+        Ok(Writer::with_codec(
+            self.schema,
+            BufWriter::new(file),
+            self.codec,
+        ))
+    }
+
+    fn close(self) -> Result<()> {
+        for (_, writer) in self.open {
+            finish(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Names of the files for each key, which stay distinct even when keys differ only in
+/// characters that aren't safe in file names
+struct FileNames {
+    by: String,
+    /// File name stem of each key seen so far, and the number of files created for it
+    stems: HashMap<String, (String, usize)>,
+    taken: HashSet<String>,
+}
+
+impl FileNames {
+    fn new(by: &str) -> Self {
+        FileNames {
+            by: sanitize(by),
+            stems: HashMap::new(),
+            taken: HashSet::new(),
+        }
+    }
+
+    /// Name of the next file for a key: its first part, then spilled parts.
+    /// The `~` and `+` separators are never in sanitized values, so no value can produce
+    /// the name of another value's file.
+    fn next(&mut self, key: &str) -> String {
+        let (stem, parts) = self.stems.entry(key.to_owned()).or_insert_with(|| {
+            let base = format!("{}={}", self.by, sanitize(key));
+            let mut stem = base.clone();
+            let mut n = 1;
+            while !self.taken.insert(stem.clone()) {
+                n += 1;
+                stem = format!("{}~{}", base, n);
+            }
+            (stem, 0)
+        });
+        let name = if *parts == 0 {
+            format!("{}.avro", stem)
+        } else {
+            format!("{}+{}.avro", stem, parts)
+        };
+        *parts += 1;
+        name
+    }
+}
+
+fn finish(writer: PartitionWriter) -> Result<()> {
+    writer
+        .into_inner()
+        .into_diagnostic()?
+        .flush()
+        .into_diagnostic()
+}
+
+/// Make a value safe to use as part of a file name
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use apache_avro::Reader;
+
+    #[test]
+    fn test_file_names() {
+        let mut names = FileNames::new("region");
+        assert_eq!(names.next("us east"), "region=us_east.avro");
+        assert_eq!(names.next("us_east"), "region=us_east~2.avro");
+        assert_eq!(names.next("us/east"), "region=us_east~3.avro");
+        assert_eq!(names.next("x"), "region=x.avro");
+        assert_eq!(names.next("x"), "region=x+1.avro");
+        assert_eq!(names.next("x.1"), "region=x.1.avro");
+        assert_eq!(names.next("us east"), "region=us_east+1.avro");
+    }
+
+    #[test]
+    fn test_split() {
+        let dir = TempDir::new("split");
+        let mut avro = CliService::from("./test_assets/bttf.avro".to_owned()).unwrap();
+        split(&mut avro, "lastName", dir.path(), 1, Codec::Null).unwrap();
+
+        let mut files: Vec<(String, usize)> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let records = Reader::new(File::open(&path).unwrap()).unwrap().count();
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, records)
+            })
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                ("lastName=Baines-McFly.avro".to_owned(), 1),
+                ("lastName=Brown.avro".to_owned(), 1),
+                ("lastName=McFly.avro".to_owned(), 1),
+                ("lastName=Tannen.avro".to_owned(), 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use crate::avro_value::AvroValue;
+use crate::cli::{CliService, GetOptions};
+use apache_avro::types::Value;
+use miette::{bail, Result};
+
+/// How aggregations treat null and missing (N/A) values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum NullHandling {
+    /// Leave them out of the aggregation
+    #[default]
+    Skip,
+    /// Count them as zero
+    Zero,
+    /// Abort the aggregation
+    Error,
+}
+
+/// Running aggregation of the numeric values of a field
+#[derive(Debug, Clone, Default)]
+pub struct Aggregate {
+    /// Number of values aggregated
+    pub count: u64,
+    /// Number of null or missing values that were skipped
+    pub skipped: u64,
+    pub sum: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Every value aggregated, only kept when percentiles are wanted
+    values: Option<Vec<f64>>,
+}
+
+impl Aggregate {
+    /// An aggregation that also keeps every value, to compute percentiles
+    pub fn with_percentiles() -> Self {
+        Aggregate {
+            values: Some(Vec::new()),
+            ..Aggregate::default()
+        }
+    }
+
+    /// Add a value to the aggregation. Fails on non-numeric values, and on
+    /// null or missing ones with `NullHandling::Error`.
+    pub fn add(&mut self, value: &AvroValue, null_handling: NullHandling) -> Result<()> {
+        let number = match value {
+            AvroValue::Na => None,
+            AvroValue::Value(value) => match numeric(value) {
+                Numeric::Number(number) => Some(number),
+                Numeric::Null => None,
+                Numeric::Other => bail!(
+                    "Cannot aggregate non-numeric value {}",
+                    AvroValue::from(value.clone())
+                ),
+            },
+        };
+
+        let number = match (number, null_handling) {
+            (Some(number), _) => number,
+            (None, NullHandling::Skip) => {
+                self.skipped += 1;
+                return Ok(());
+            }
+            (None, NullHandling::Zero) => 0.0,
+            (None, NullHandling::Error) => bail!("Cannot aggregate a null or missing value"),
+        };
+
+        self.count += 1;
+        self.sum += number;
+        self.min = Some(self.min.map_or(number, |min| min.min(number)));
+        self.max = Some(self.max.map_or(number, |max| max.max(number)));
+        if let Some(values) = &mut self.values {
+            values.push(number);
+        }
+        Ok(())
+    }
+
+    /// Exact percentiles, each between 0 and 100, by the nearest-rank method: the smallest
+    /// value that at least that percentage of the values are less than or equal to.
+    ///
+    /// The kept values are sorted in place. None when there are no values, or the
+    /// aggregation wasn't made `with_percentiles`.
+    pub fn percentiles(&mut self, percentiles: &[f64]) -> Vec<Option<f64>> {
+        let values = match &mut self.values {
+            Some(values) if !values.is_empty() => values,
+            _ => return vec![None; percentiles.len()],
+        };
+        values.sort_by(f64::total_cmp);
+        percentiles
+            .iter()
+            .map(|percentile| {
+                let rank = (percentile / 100.0 * values.len() as f64).ceil() as usize;
+                Some(values[rank.clamp(1, values.len()) - 1])
+            })
+            .collect()
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+/// Aggregate the numeric values of each field over every record.
+///
+/// With `percentiles`, every value is kept in memory, 8 bytes each, so that the
+/// percentiles are exact.
+pub fn stats(
+    avro: &mut CliService,
+    fields: &[String],
+    options: &GetOptions,
+    null_handling: NullHandling,
+    percentiles: bool,
+) -> Result<Vec<(String, Aggregate)>> {
+    let aggregate = if percentiles {
+        Aggregate::with_percentiles()
+    } else {
+        Aggregate::default()
+    };
+    let mut aggregates = vec![aggregate; fields.len()];
+    for (i, row) in avro.get_fields(fields, options)?.into_iter().enumerate() {
+        for (aggregate, value) in aggregates.iter_mut().zip(&row) {
+            aggregate.add(value.value(), null_handling).map_err(|err| {
+                err.wrap_err(format!("In field `{}` of record {}", value.name(), i))
+            })?;
+        }
+    }
+
+    Ok(fields.iter().cloned().zip(aggregates).collect())
+}
+
+enum Numeric {
+    Number(f64),
+    Null,
+    Other,
+}
+
+fn numeric(value: &Value) -> Numeric {
+    match value {
+        Value::Int(i) => Numeric::Number(*i as f64),
+        Value::Long(l) => Numeric::Number(*l as f64),
+        Value::Float(f) => Numeric::Number(*f as f64),
+        Value::Double(d) => Numeric::Number(*d),
+        Value::Null => Numeric::Null,
+        Value::Union(_, value) => numeric(value),
+        _ => Numeric::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let mut aggregate = Aggregate::with_percentiles();
+        for age in [24, 72, 65, 62] {
+            let value = AvroValue::from(Value::Int(age));
+            aggregate.add(&value, NullHandling::Skip).unwrap();
+        }
+        assert_eq!(
+            aggregate.percentiles(&[0.0, 50.0, 90.0, 100.0]),
+            vec![Some(24.0), Some(62.0), Some(72.0), Some(72.0)]
+        );
+        assert_eq!(Aggregate::default().percentiles(&[50.0]), vec![None]);
+    }
+}
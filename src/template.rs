@@ -0,0 +1,110 @@
+use crate::avro_value::{FormatOptions, NA};
+use crate::cli::AvroColumnarValue;
+use miette::{bail, Result};
+
+/// A line of text with `{field}` placeholders, rendered once per record.
+/// `{{` and `}}` stand for literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field(String),
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => bail!("Unclosed placeholder `{{{}` in template", field),
+                        }
+                    }
+                    if field.is_empty() {
+                        bail!("Empty placeholder `{{}}` in template");
+                    }
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                    parts.push(Part::Field(field));
+                }
+                '}' => bail!("Unmatched `}}` in template, write `}}}}` for a literal brace"),
+                c => text.push(c),
+            }
+        }
+        parts.push(Part::Text(text));
+        parts.retain(|part| part != &Part::Text(String::new()));
+        Ok(Template { parts })
+    }
+
+    /// Names of the fields the placeholders refer to, each once, in order
+    pub fn fields(&self) -> Vec<String> {
+        let mut fields: Vec<String> = Vec::new();
+        for part in &self.parts {
+            if let Part::Field(field) = part {
+                if !fields.contains(field) {
+                    fields.push(field.clone());
+                }
+            }
+        }
+        fields
+    }
+
+    /// Render the template for a record. Fields the record doesn't have are `N/A`
+    pub fn render(&self, row: &[AvroColumnarValue], options: &FormatOptions) -> Result<String> {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Field(field) => match row.iter().find(|value| value.name() == field) {
+                    Some(value) => rendered.push_str(&value.value().format(options)?),
+                    None => rendered.push_str(NA),
+                },
+            }
+        }
+        Ok(rendered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::avro_value::AvroValue;
+    use apache_avro::types::Value;
+
+    #[test]
+    fn test_template() {
+        let template = Template::parse("user {firstName} is {age} {{{age}}}").unwrap();
+        assert_eq!(template.fields(), vec!["firstName", "age"]);
+
+        let row = vec![AvroColumnarValue::from(
+            "firstName".to_owned(),
+            AvroValue::from(Value::String("Marty".to_owned())),
+        )];
+        assert_eq!(
+            template.render(&row, &FormatOptions::default()).unwrap(),
+            "user Marty is N/A {N/A}"
+        );
+
+        assert!(Template::parse("{unclosed").is_err());
+        assert!(Template::parse("{}").is_err());
+        assert!(Template::parse("stray }").is_err());
+    }
+}